@@ -2,11 +2,19 @@
 extern crate pipewrench;
 extern crate sdl2;
 extern crate glium;
+#[macro_use]
+extern crate serde;
 
 use sdl2::event::Event;
 use pipewrench::{Window, Input, BindingState, Keycode};
-use pipewrench::config::Config;
+use pipewrench::config::{Config, KeySchema, Layer, Schema};
 use glium::Surface;
+#[derive(Debug,Deserialize)]
+struct WindowConfig {
+    width: u32,
+    height: u32
+}
+
 #[derive(Debug,Clone)]
 enum Command {
     Forward,
@@ -17,21 +25,31 @@ enum Command {
 }
 
 fn main() {
-    let config = main_try!(Config::from_file("./examples/config.toml"));
+    let schema = Schema::new()
+        .with_key("window.width", KeySchema::integer().with_range(320.0, 7680.0).with_default(1280).with_description("Window width in pixels"))
+        .with_key("window.height", KeySchema::integer().with_range(240.0, 4320.0).with_default(720).with_description("Window height in pixels"))
+        .with_key("window.fullscreen", KeySchema::boolean().with_default(false))
+        .with_key("keybind.*", KeySchema::string());
+    let mut config = main_try!(Config::new().with_defaults(&schema).with_file(Layer::Game, "./examples/config.toml"));
+    if let Err(errors) = schema.validate(&config) {
+        for e in errors {
+            println!("{}", e);
+        }
+    }
     let sdl = main_try!(sdl2::init());
     let video = main_try!(sdl.video());
-    let win = main_try!(Window::new(&video, "Simple Window",
-                                    config.value_int("window.width").unwrap_or(1280) as u32,
-                                    config.value_int("window.height").unwrap_or(720) as u32));
+    let size = main_try!(config.get::<WindowConfig>("window"));
+    let win = main_try!(Window::new(&video, "Simple Window", size.width, size.height));
     let mut running = true;
 
     let mut event_pump = main_try!(sdl.event_pump());
     let mut input = Input::<Command>::new(None)
-            .add_binding(config.keybinding("keybind.Forward", BindingState::Held), Command::Forward)
-            .add_binding(config.keybinding("keybind.Left", BindingState::Held), Command::Left)
-            .add_binding(config.keybinding("keybind.Right", BindingState::Held), Command::Right)
-            .add_binding(config.keybinding("keybind.Back", BindingState::Held), Command::Back)
-            .add_binding(config.keybinding("keybind.Fire", BindingState::Pressed), Command::Fire);
+            .add_config_binding(&mut config, "keybind.Forward", BindingState::Held, Command::Forward)
+            .add_config_binding(&mut config, "keybind.Left", BindingState::Held, Command::Left)
+            .add_config_binding(&mut config, "keybind.Right", BindingState::Held, Command::Right)
+            .add_config_binding(&mut config, "keybind.Back", BindingState::Held, Command::Back)
+            .add_config_binding(&mut config, "keybind.Fire", BindingState::Pressed, Command::Fire);
+    config.watch(true);
 
     while running {
         let mut target = win.draw();
@@ -45,6 +63,8 @@ fn main() {
             }
         }
 
+        config.reload_modified();
+        input.mouse_state(event_pump.mouse_state());
         input.key_state(event_pump.keyboard_state());
         for command in input.command_iter() {
             println!("{:?}", command);
@@ -0,0 +1,228 @@
+use cgmath::BaseFloat;
+use cgmath::Point2 as Pnt2;
+use cgmath::num_traits::cast;
+use std::fmt::Write;
+use super::{AABB2, Circle, Line, Intersection, QuadTree, SpacialIndex};
+
+///One end of a debug line, pairs of these make a line list
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct DebugVertex {
+    pub position: [f32; 2],
+    pub color: [f32; 4]
+}
+
+///Anything that can turn itself into debug lines
+pub trait DebugDraw {
+    fn debug_draw(&self, lines: &mut DebugLines, color: [f32; 4]);
+}
+
+///Line list vertex data for collision debugging
+#[derive(Debug,Clone)]
+pub struct DebugLines {
+    vertices: Vec<DebugVertex>,
+    circle_segments: usize,
+    marker_size: f32
+}
+
+fn to_f32<S: BaseFloat>(p: &Pnt2<S>) -> [f32; 2] {
+    [cast(p.x).unwrap_or(0.0), cast(p.y).unwrap_or(0.0)]
+}
+
+impl DebugLines {
+    pub fn new() -> DebugLines {
+        DebugLines {
+            vertices: Vec::new(),
+            circle_segments: 32,
+            marker_size: 0.25
+        }
+    }
+
+    ///How many segments circles are drawn with
+    pub fn with_segments(mut self, segments: usize) -> Self {
+        self.circle_segments = segments.max(3);
+        self
+    }
+
+    ///Half the width of the crosses used to mark points
+    pub fn with_marker_size(mut self, size: f32) -> Self {
+        self.marker_size = size;
+        self
+    }
+
+    pub fn line(&mut self, a: [f32; 2], b: [f32; 2], color: [f32; 4]) {
+        self.vertices.push(DebugVertex { position: a, color });
+        self.vertices.push(DebugVertex { position: b, color });
+    }
+
+    ///Mark a point with a small cross
+    pub fn marker(&mut self, p: [f32; 2], color: [f32; 4]) {
+        let s = self.marker_size;
+        self.line([p[0] - s, p[1] - s], [p[0] + s, p[1] + s], color);
+        self.line([p[0] - s, p[1] + s], [p[0] + s, p[1] - s], color);
+    }
+
+    pub fn draw<D: DebugDraw>(&mut self, shape: &D, color: [f32; 4]) {
+        shape.debug_draw(self, color);
+    }
+
+    pub fn vertices(&self) -> &[DebugVertex] {
+        &self.vertices[..]
+    }
+
+    pub fn len(&self) -> usize {
+        self.vertices.len() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    ///Smallest box holding every vertex, (min, max)
+    pub fn bounds(&self) -> Option<([f32; 2], [f32; 2])> {
+        let first = self.vertices.first()?.position;
+        Some(self.vertices.iter().fold((first, first), |(min, max), v| {
+            ([min[0].min(v.position[0]), min[1].min(v.position[1])],
+             [max[0].max(v.position[0]), max[1].max(v.position[1])])
+        }))
+    }
+
+    ///Render to an SVG document, y is flipped so up stays up
+    pub fn to_svg(&self) -> String {
+        let (min, max) = self.bounds().unwrap_or(([0.0, 0.0], [0.0, 0.0]));
+        let margin = self.marker_size;
+        let mut svg = String::new();
+
+        let _ = writeln!(svg, "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">",
+                         min[0] - margin, -max[1] - margin,
+                         max[0] - min[0] + margin * 2.0, max[1] - min[1] + margin * 2.0);
+        for pair in self.vertices.chunks(2) {
+            if pair.len() < 2 { break }
+            let (a, b) = (pair[0].position, pair[1].position);
+            let c = pair[0].color;
+            let _ = writeln!(svg, "  <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"rgb({},{},{})\" stroke-opacity=\"{}\" vector-effect=\"non-scaling-stroke\"/>",
+                             a[0], -a[1], b[0], -b[1],
+                             (c[0] * 255.0) as u8, (c[1] * 255.0) as u8, (c[2] * 255.0) as u8, c[3]);
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+impl Default for DebugLines {
+    fn default() -> DebugLines {
+        DebugLines::new()
+    }
+}
+
+impl<S> DebugDraw for Pnt2<S> where S: BaseFloat {
+    fn debug_draw(&self, lines: &mut DebugLines, color: [f32; 4]) {
+        lines.marker(to_f32(self), color);
+    }
+}
+
+impl<S> DebugDraw for Line<S> where S: BaseFloat {
+    fn debug_draw(&self, lines: &mut DebugLines, color: [f32; 4]) {
+        lines.line(to_f32(&self.a), to_f32(&self.b), color);
+    }
+}
+
+impl<S> DebugDraw for AABB2<S> where S: BaseFloat {
+    fn debug_draw(&self, lines: &mut DebugLines, color: [f32; 4]) {
+        let tl = to_f32(&self.tl);
+        let br = to_f32(&self.br);
+        let tr = [br[0], tl[1]];
+        let bl = [tl[0], br[1]];
+
+        lines.line(tl, tr, color);
+        lines.line(tr, br, color);
+        lines.line(br, bl, color);
+        lines.line(bl, tl, color);
+    }
+}
+
+impl<S> DebugDraw for Circle<S> where S: BaseFloat {
+    fn debug_draw(&self, lines: &mut DebugLines, color: [f32; 4]) {
+        let c = to_f32(&self.pos);
+        let r:f32 = cast(self.radius).unwrap_or(0.0);
+        let n = lines.circle_segments;
+        let step = ::std::f32::consts::PI * 2.0 / n as f32;
+        let at = |i: usize| [c[0] + r * (step * i as f32).cos(), c[1] + r * (step * i as f32).sin()];
+
+        for i in 0..n {
+            let (a, b) = (at(i), at(i + 1));
+            lines.line(a, b, color);
+        }
+    }
+}
+
+impl<S> DebugDraw for Intersection<S> where S: BaseFloat {
+    fn debug_draw(&self, lines: &mut DebugLines, color: [f32; 4]) {
+        match *self {
+            Intersection::Overlap(ref a, ref b) => {
+                lines.line(to_f32(a), to_f32(b), color);
+                lines.marker(to_f32(a), color);
+                lines.marker(to_f32(b), color);
+            },
+            Intersection::Intersects(ref a, ref b) => {
+                lines.marker(to_f32(a), color);
+                if let Some(ref b) = *b {
+                    lines.marker(to_f32(b), color);
+                }
+            },
+            Intersection::IntersectsN(ref p) => for x in p {
+                lines.marker(to_f32(x), color);
+            },
+            _ => {}
+        }
+    }
+}
+
+///Draws every node's volume and marks every object's position
+impl<S, T> DebugDraw for QuadTree<S, T> where T: SpacialIndex, S: BaseFloat {
+    fn debug_draw(&self, lines: &mut DebugLines, color: [f32; 4]) {
+        self.volume().debug_draw(lines, color);
+        for obj in self.bucket() {
+            obj.get_position::<S>().debug_draw(lines, color);
+        }
+        if let Some(children) = self.children() {
+            for quad in children.iter() {
+                quad.debug_draw(lines, color);
+            }
+        }
+    }
+}
+
+#[test]
+fn debug_svg() {
+    use std::sync::Arc;
+
+    #[derive(Debug)]
+    struct Dot(Pnt2<f32>);
+    impl SpacialIndex for Dot {
+        fn get_position<T: BaseFloat>(&self) -> Pnt2<T> {
+            Pnt2::new(T::from(self.0.x).unwrap(), T::from(self.0.y).unwrap())
+        }
+    }
+
+    let mut lines = DebugLines::new();
+    lines.draw(&AABB2::new(Pnt2::new(-1.0, 1.0), Pnt2::new(1.0, -1.0)), [1.0, 0.0, 0.0, 1.0]);
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines.bounds(), Some(([-1.0, -1.0], [1.0, 1.0])));
+
+    let svg = lines.to_svg();
+    assert!(svg.starts_with("<svg"));
+    assert_eq!(svg.matches("<line").count(), 4);
+    assert!(svg.contains("x1=\"-1\" y1=\"-1\" x2=\"1\" y2=\"-1\" stroke=\"rgb(255,0,0)\""));
+
+    //a split root is 5 boxes plus a marker per object
+    let mut qt = QuadTree::<f32, Dot>::with_capacity(AABB2::new(Pnt2::new(-10.0, 10.0), Pnt2::new(10.0, -10.0)), 1);
+    qt.insert(&Arc::new(Dot(Pnt2::new(-5.0, 5.0)))).unwrap();
+    qt.insert(&Arc::new(Dot(Pnt2::new(5.0, -5.0)))).unwrap();
+    lines.clear();
+    lines.draw(&qt, [0.0, 1.0, 0.0, 1.0]);
+    assert_eq!(lines.len(), 5 * 4 + 2 * 2);
+}
@@ -1,9 +1,15 @@
 mod quadtree;
 mod primitive;
+mod debug;
+#[cfg(feature = "window")]
+mod render;
 use cgmath::Point2 as Pnt2;
 use cgmath::BaseFloat;
 pub use self::quadtree::*;
 pub use self::primitive::*;
+pub use self::debug::*;
+#[cfg(feature = "window")]
+pub use self::render::*;
 
 pub trait Intersect<T, S> where S: BaseFloat {
     fn intersection(&self, other: &T) -> Intersection<S>;
@@ -12,6 +18,7 @@ pub trait Intersect<T, S> where S: BaseFloat {
 }
 
 #[derive(Debug,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Intersection<S> where S: BaseFloat {
     Outside,
     Inside,
@@ -6,6 +6,7 @@ use super::super::cgmath_augment::*;
 use super::{Intersect, Intersection};
 
 #[derive(Debug,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Circle<S: BaseFloat> {
     pub pos: Pnt2<S>,
     pub radius: S
@@ -13,12 +14,14 @@ pub struct Circle<S: BaseFloat> {
 
 ///Lines go from a to b
 #[derive(Debug,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Line<S: BaseFloat> {
     pub a: Pnt2<S>,
     pub b: Pnt2<S>
 }
 
 #[derive(Debug,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct AABB2<T: BaseFloat> {
     pub tl: Pnt2<T>,
     pub br: Pnt2<T>
@@ -64,8 +67,11 @@ impl<S> Intersect<Pnt2<S>, S> for AABB2<S> where S: BaseFloat {
     }
 
     fn intersects(&self, other: &Pnt2<S>) -> bool {
-        other.x == self.tl.x || other.y == self.tl.y ||
-        other.x == self.br.x || other.y == self.br.y
+        //on an edge, not just in line with one
+        other.x >= self.tl.x && other.x <= self.br.x &&
+        other.y <= self.tl.y && other.y >= self.br.y &&
+        (other.x == self.tl.x || other.y == self.tl.y ||
+         other.x == self.br.x || other.y == self.br.y)
     }
 
     fn contains(&self, other: &Pnt2<S>) -> bool {
@@ -324,6 +330,90 @@ impl<S> Intersect<AABB2<S>, S> for Circle<S> where S: BaseFloat {
     }
 }
 
+impl<S> Intersect<AABB2<S>, S> for AABB2<S> where S: BaseFloat {
+    fn intersection(&self, other: &AABB2<S>) -> Intersection<S> {
+        if other.br.x < self.tl.x || other.tl.x > self.br.x ||
+           other.br.y > self.tl.y || other.tl.y < self.br.y {
+            return Intersection::Outside
+        }
+
+        if other.tl.x >= self.tl.x && other.br.x <= self.br.x &&
+           other.tl.y <= self.tl.y && other.br.y >= self.br.y {
+            return Intersection::Inside
+        }
+
+        if self.tl.x >= other.tl.x && self.br.x <= other.br.x &&
+           self.tl.y <= other.tl.y && self.br.y >= other.br.y {
+            return Intersection::InverseContain
+        }
+
+        //the overlapping region, same orientation as the boxes
+        Intersection::Overlap(Pnt2::new(self.tl.x.max(other.tl.x), self.tl.y.min(other.tl.y)),
+                              Pnt2::new(self.br.x.min(other.br.x), self.br.y.max(other.br.y)))
+    }
+
+    fn intersects(&self, other: &AABB2<S>) -> bool {
+        match self.intersection(other) {
+            Intersection::Outside => false,
+            _ => true
+        }
+    }
+
+    fn contains(&self, other: &AABB2<S>) -> bool {
+        match self.intersection(other) {
+            Intersection::Inside => true,
+            _ => false
+        }
+    }
+}
+
+impl<S> Intersect<AABB2<S>, S> for Line<S> where S: BaseFloat {
+    fn intersection(&self, other: &AABB2<S>) -> Intersection<S> {
+        if other.contains(&self.a) && other.contains(&self.b) {
+            return Intersection::Inside
+        }
+
+        let edges = [
+            Line::new(other.tl, Pnt2::new(other.br.x, other.tl.y)), //top
+            Line::new(Pnt2::new(other.br.x, other.tl.y), other.br), //right
+            Line::new(other.br, Pnt2::new(other.tl.x, other.br.y)), //bottom
+            Line::new(Pnt2::new(other.tl.x, other.br.y), other.tl), //left
+        ];
+        let mut p:Vec<Pnt2<S>> = Vec::new();
+        //a corner is on two edges, only report it once
+        let mut add = |point: Pnt2<S>| if !p.contains(&point) { p.push(point) };
+
+        for edge in edges.iter() {
+            match self.intersection(edge) {
+                Intersection::Intersects(a, _) => add(a),
+                Intersection::Overlap(a, b) => {
+                    add(a);
+                    add(b);
+                },
+                _ => {}
+            }
+        }
+
+        if p.is_empty() {
+            return Intersection::Outside
+        }
+
+        Intersection::IntersectsN(p)
+    }
+
+    fn intersects(&self, other: &AABB2<S>) -> bool {
+        match self.intersection(other) {
+            Intersection::IntersectsN(_) | Intersection::Inside => true,
+            _ => false
+        }
+    }
+
+    fn contains(&self, _: &AABB2<S>) -> bool {
+        //a line can't contain an area
+        false
+    }
+}
+
 #[test]
 fn line_intersection() {
     let mut l1 = Line::new(Pnt2::new(-5.0, 5.0), Pnt2::new(-1.0, 5.0));
@@ -454,3 +544,46 @@ fn circle_aabb2_intersection() {
     assert_eq!(circle.intersection(&aabb), Intersection::InverseContain);
 }
 
+
+#[test]
+fn aabb2_aabb2_intersection() {
+    let aabb = AABB2::new(Pnt2::new(-5.0, 5.0), Pnt2::new(5.0, -5.0));
+
+    //fully inside
+    assert_eq!(aabb.intersection(&AABB2::new(Pnt2::new(-1.0, 1.0), Pnt2::new(1.0, -1.0))), Intersection::Inside);
+    //other way around
+    assert_eq!(AABB2::new(Pnt2::new(-1.0, 1.0), Pnt2::new(1.0, -1.0)).intersection(&aabb), Intersection::InverseContain);
+
+    //overlapping corner
+    assert_eq!(aabb.intersection(&AABB2::new(Pnt2::new(3.0, 8.0), Pnt2::new(8.0, 3.0))),
+               Intersection::Overlap(Pnt2::new(3.0, 5.0), Pnt2::new(5.0, 3.0)));
+
+    //outside
+    assert_eq!(aabb.intersection(&AABB2::new(Pnt2::new(6.0, 8.0), Pnt2::new(8.0, 6.0))), Intersection::Outside);
+}
+
+#[test]
+fn line_aabb2_intersection() {
+    let aabb = AABB2::new(Pnt2::new(-5.0, 5.0), Pnt2::new(5.0, -5.0));
+    let mut line = Line::new(Pnt2::new(-10.0, 0.0), Pnt2::new(10.0, 0.0));
+
+    //straight through
+    assert_eq!(line.intersection(&aabb), Intersection::IntersectsN(vec![Pnt2::new(5.0, 0.0), Pnt2::new(-5.0, 0.0)]));
+
+    //inside
+    line.a = Pnt2::new(-1.0, 0.0);
+    line.b = Pnt2::new(1.0, 0.0);
+    assert_eq!(line.intersection(&aabb), Intersection::Inside);
+    assert!(line.intersects(&aabb));
+
+    //through a corner, reported once
+    line.a = Pnt2::new(0.0, 10.0);
+    line.b = Pnt2::new(10.0, 0.0);
+    assert_eq!(line.intersection(&aabb), Intersection::IntersectsN(vec![Pnt2::new(5.0, 5.0)]));
+
+    //outside
+    line.a = Pnt2::new(-10.0, 10.0);
+    line.b = Pnt2::new(10.0, 10.0);
+    assert_eq!(line.intersection(&aabb), Intersection::Outside);
+    assert!(!line.intersects(&aabb));
+}
@@ -1,26 +1,118 @@
-use std::rc::Rc;
+use std::sync::Arc;
 use cgmath::BaseFloat as SpacialKey;
-use super::{AABB2, Intersect, Intersection, Circle};
+use super::{AABB2, Intersect, Intersection, Circle, Line};
 use cgmath::Point2 as Pnt2;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
 pub trait SpacialIndex {
     fn get_position<T: SpacialKey>(&self) -> Pnt2<T>;
 }
+
+///Broadphase query shapes, see `QuadTree::query`
+#[derive(Debug,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Query<S> where S: SpacialKey {
+    Radius(Circle<S>),
+    Area(AABB2<S>),
+    Ray(Line<S>),
+}
+
+impl<S> Query<S> where S: SpacialKey {
+    fn touches(&self, volume: &AABB2<S>) -> bool {
+        let i = match *self {
+            Query::Radius(ref c) => c.intersection(volume),
+            Query::Area(ref a) => a.intersection(volume),
+            Query::Ray(ref l) => l.intersection(volume),
+        };
+        i != Intersection::Outside
+    }
+}
+
+///Subdivisions allowed below the root unless `with_max_depth` says otherwise
+pub const DEFAULT_MAX_DEPTH: usize = 16;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum QuadTreeError {
+    #[error("Position is outside the tree and the tree can't grow")]
+    OutOfBounds,
+    #[error("Position isn't a finite number")]
+    InvalidPosition,
+    #[error("Nothing stored at the old position")]
+    NotFound
+}
+
+///Serializes as its `QuadTreeContents`, deserializing inserts everything again so the nodes always match the objects
 #[derive(Debug)]
 pub struct QuadTree<S, T> where T: SpacialIndex + Sized, S: SpacialKey {
-    bucket: Vec<Rc<T>>,
+    bucket: Vec<Arc<T>>,
     //NW, NE, SE, SW
     children: Option<[Box<QuadTree<S, T>>; 4]>,
     capacity: usize,
+    //how many more times this node may subdivide, at 0 the bucket just overflows
+    depth_limit: usize,
+    //only the root grows
+    grow: bool,
     volume: AABB2<S>
 }
 
+///Tree health, see `QuadTree::stats`
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct QuadTreeStats {
+    ///Deepest level below the root, 0 for a lone root
+    pub depth: usize,
+    pub nodes: usize,
+    pub leaves: usize,
+    pub items: usize,
+    ///Objects held at each level, starting at the root
+    pub items_per_level: Vec<usize>
+}
+
+///Just the objects in a tree, serializes smaller than the tree itself
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct QuadTreeContents<S, T> where S: SpacialKey {
+    pub volume: AABB2<S>,
+    pub capacity: usize,
+    pub max_depth: usize,
+    pub items: Vec<Arc<T>>
+}
+
 impl<'a, S, T> QuadTree<S, T> where T: SpacialIndex + Sized, S: SpacialKey {
     pub fn with_capacity(volume: AABB2<S>, capacity: usize) -> QuadTree<S, T> {
+        let mut qt = QuadTree::node(volume, capacity, DEFAULT_MAX_DEPTH);
+        qt.grow = true;
+        qt
+    }
+
+    ///Limit how deep the tree subdivides, nodes that already exist take the new limit too
+    pub fn with_max_depth(mut self, depth: usize) -> Self {
+        self.limit_depth(depth);
+        self
+    }
+
+    fn limit_depth(&mut self, depth: usize) {
+        self.depth_limit = depth;
+        if let Some(ref mut quad) = self.children {
+            for q in quad.iter_mut() {
+                q.limit_depth(depth.saturating_sub(1));
+            }
+        }
+    }
+
+    ///Whether the root grows to fit objects outside of it, on by default
+    pub fn growable(mut self, grow: bool) -> Self {
+        self.grow = grow;
+        self
+    }
+
+    fn node(volume: AABB2<S>, capacity: usize, depth_limit: usize) -> QuadTree<S, T> {
         QuadTree {
             bucket: Vec::with_capacity(capacity),
             children: None,
             capacity,
+            depth_limit,
+            grow: false,
             volume
         }
     }
@@ -31,36 +123,98 @@ impl<'a, S, T> QuadTree<S, T> where T: SpacialIndex + Sized, S: SpacialKey {
 
         let hw = (qt.volume.br.x - qt.volume.tl.x)/S::from(2.0).unwrap().abs();
         let hh = (qt.volume.br.y - qt.volume.tl.y)/S::from(2.0).unwrap().abs();
+        let depth = qt.depth_limit.saturating_sub(1);
 
         qt.children = Some(
             [
-                Box::new(QuadTree::<S,T>::with_capacity(AABB2::<S>::new(Pnt2::new(min.x, min.y), Pnt2::new(min.x + hw, min.y + hh)), qt.capacity)),
-                Box::new(QuadTree::<S,T>::with_capacity(AABB2::<S>::new(Pnt2::new(min.x + hw, min.y), Pnt2::new(max.x, min.y + hh)), qt.capacity)),
-                Box::new(QuadTree::<S,T>::with_capacity(AABB2::<S>::new(Pnt2::new(min.x + hw, min.y + hh), Pnt2::new(max.x, max.y)), qt.capacity)),
-                Box::new(QuadTree::<S,T>::with_capacity(AABB2::<S>::new(Pnt2::new(min.x, min.y + hh), Pnt2::new(min.x + hw, max.y)), qt.capacity)),
+                Box::new(QuadTree::<S,T>::node(AABB2::<S>::new(Pnt2::new(min.x, min.y), Pnt2::new(min.x + hw, min.y + hh)), qt.capacity, depth)),
+                Box::new(QuadTree::<S,T>::node(AABB2::<S>::new(Pnt2::new(min.x + hw, min.y), Pnt2::new(max.x, min.y + hh)), qt.capacity, depth)),
+                Box::new(QuadTree::<S,T>::node(AABB2::<S>::new(Pnt2::new(min.x + hw, min.y + hh), Pnt2::new(max.x, max.y)), qt.capacity, depth)),
+                Box::new(QuadTree::<S,T>::node(AABB2::<S>::new(Pnt2::new(min.x, min.y + hh), Pnt2::new(min.x + hw, max.y)), qt.capacity, depth)),
             ]
         );
     }
 
-    pub fn insert(&mut self, obj: &Rc<T>) -> bool {
-        let pos = obj.get_position();
+    ///Double the root towards `pos`, the old root becomes one of the new root's children
+    fn grow_towards(&mut self, pos: &Pnt2<S>) -> Result<(), QuadTreeError> {
+        let (tl, br) = (self.volume.tl, self.volume.br);
+        let w = br.x - tl.x;
+        let h = br.y - tl.y;
+
+        if w == S::zero() || h == S::zero() {
+            return Err(QuadTreeError::OutOfBounds)
+        }
+
+        //past the tl edge means growing that way, works whichever way y points
+        let left = (pos.x - tl.x) * w < S::zero();
+        let up = (pos.y - tl.y) * h < S::zero();
+
+        let volume = AABB2::new(Pnt2::new(if left { tl.x - w } else { tl.x }, if up { tl.y - h } else { tl.y }),
+                                Pnt2::new(if left { br.x } else { br.x + w }, if up { br.y } else { br.y + h }));
+        if !(volume.tl.x.is_finite() && volume.tl.y.is_finite() && volume.br.x.is_finite() && volume.br.y.is_finite()) {
+            return Err(QuadTreeError::OutOfBounds)
+        }
 
-        if self.volume.intersection(&pos) == Intersection::Outside {
+        //the old root sits in the corner opposite the growth
+        let slot = match (left, up) {
+            (false, false) => 0,
+            (true, false) => 1,
+            (true, true) => 2,
+            (false, true) => 3,
+        };
+
+        let mut root = QuadTree::node(volume, self.capacity, self.depth_limit + 1);
+        root.grow = true;
+        Self::subdivide(&mut root);
+
+        let mut old = ::std::mem::replace(self, root);
+        old.grow = false;
+        *self.children.as_mut().unwrap()[slot] = old;
+        Ok(())
+    }
+
+    pub fn insert(&mut self, obj: &Arc<T>) -> Result<(), QuadTreeError> {
+        let pos = obj.get_position::<S>();
+
+        if !pos.x.is_finite() || !pos.y.is_finite() {
+            return Err(QuadTreeError::InvalidPosition)
+        }
+
+        while self.volume.intersection(&pos) == Intersection::Outside {
+            if !self.grow {
+                return Err(QuadTreeError::OutOfBounds)
+            }
+            self.grow_towards(&pos)?;
+        }
+
+        if self.insert_at(obj, &pos) {
+            Ok(())
+        }
+        else {
+            Err(QuadTreeError::OutOfBounds)
+        }
+    }
+
+    fn insert_at(&mut self, obj: &Arc<T>, pos: &Pnt2<S>) -> bool {
+        if self.volume.intersection(pos) == Intersection::Outside {
             return false
         }
 
-        if self.bucket.len() == self.capacity {
+        if self.children.is_none() && self.bucket.len() >= self.capacity && self.depth_limit > 0 {
             Self::subdivide(self);
 
-            for _ in 0..self.bucket.len() {
-                let val = self.bucket.pop().unwrap();
-                self.insert(&val);
+            let old = ::std::mem::take(&mut self.bucket);
+            let placed = old.iter().all(|val| self.insert_at(val, &val.get_position()));
+            //something the children wouldn't take, keep this a leaf rather than lose it
+            if !placed {
+                self.children = None;
+                self.bucket = old;
             }
         }
 
         match self.children {
             Some(ref mut quad) => for node in quad.iter_mut() {
-                if node.insert(obj) {
+                if node.insert_at(obj, pos) {
                     return true
                 }
             },
@@ -73,44 +227,173 @@ impl<'a, S, T> QuadTree<S, T> where T: SpacialIndex + Sized, S: SpacialKey {
         false
     }
 
-    pub fn remove_key(&mut self, old: &Pnt2<S>) -> Option<Rc<T>> {
-        if self.volume.intersection(&old) == Intersection::Outside {
+    pub fn remove_key(&mut self, old: &Pnt2<S>) -> Option<Arc<T>> {
+        if self.volume.intersection(old) == Intersection::Outside {
             return None;
         }
 
-        if self.children.is_none() {
-            for i in 0..self.bucket.len() {
-                if self.bucket[i].get_position() == *old {
-                    return Some(self.bucket.remove(i))
+        let mut ret = None;
+        match self.children {
+            Some(ref mut quad) => for node in quad.iter_mut() {
+                ret = node.remove_key(old);
+                if ret.is_some() {
+                    break
                 }
+            },
+            None => if let Some(i) = self.bucket.iter().position(|x| x.get_position() == *old) {
+                return Some(self.bucket.remove(i))
             }
         }
 
-        for i in 0..4 {
-            if let Some(ret) = self.children.as_mut().unwrap().get_mut(i).unwrap().remove_key(old) {
-                    return Some(ret);
+        if ret.is_some() {
+            self.collapse();
+        }
+        ret
+    }
+
+    ///Fold leaf children back into this node once they hold less than `capacity` between them
+    fn collapse(&mut self) -> bool {
+        let total = match self.children {
+            Some(ref quad) if quad.iter().all(|q| q.children.is_none()) => quad.iter().map(|q| q.bucket.len()).sum::<usize>(),
+            _ => return false
+        };
+
+        if total >= self.capacity {
+            return false
+        }
+
+        if let Some(mut quad) = self.children.take() {
+            for q in quad.iter_mut() {
+                self.bucket.append(&mut q.bucket);
             }
         }
-        None
+        true
     }
 
-    pub fn update(&mut self, old: &Pnt2<S>, new: &Rc<T>) -> bool {
-        if let Some(_) = self.remove_key(old) {
-            return self.insert(new)
+    ///Collapse every node that can be, bottom up
+    pub fn optimize(&mut self) {
+        if let Some(ref mut quad) = self.children {
+            for q in quad.iter_mut() {
+                q.optimize();
+            }
         }
-        return false;
+        self.collapse();
     }
 
-    pub fn get_in_radius(&self, at: &Circle<S>) -> Option<Vec<Rc<T>>> {
-        match at.intersection(&self.volume) {
-            Intersection::Outside => return None,
-            _ => {}
+    ///Throw away the node structure and insert everything again, returns whatever didn't fit
+    pub fn rebuild(&mut self) -> Vec<Arc<T>> {
+        let items = self.items();
+        self.children = None;
+        self.bucket = Vec::with_capacity(self.capacity);
+
+        items.into_iter().filter(|x| self.insert(x).is_err()).collect()
+    }
+
+    pub fn stats(&self) -> QuadTreeStats {
+        let mut stats = QuadTreeStats::default();
+        self.gather(0, &mut stats);
+        stats
+    }
+
+    fn gather(&self, level: usize, stats: &mut QuadTreeStats) {
+        if stats.items_per_level.len() <= level {
+            stats.items_per_level.push(0);
+        }
+        stats.depth = stats.depth.max(level);
+        stats.nodes += 1;
+        stats.items += self.bucket.len();
+        stats.items_per_level[level] += self.bucket.len();
+
+        match self.children {
+            Some(ref quad) => for q in quad.iter() {
+                q.gather(level + 1, stats);
+            },
+            None => stats.leaves += 1
+        }
+    }
+
+    pub fn update(&mut self, old: &Pnt2<S>, new: &Arc<T>) -> Result<(), QuadTreeError> {
+        match self.remove_key(old) {
+            Some(_) => self.insert(new),
+            None => Err(QuadTreeError::NotFound)
         }
-        let mut ret:Vec<Rc<T>> = Vec::new();
+    }
+
+    pub fn volume(&self) -> &AABB2<S> {
+        &self.volume
+    }
+
+    ///Objects held directly by this node, empty once it has children
+    pub fn bucket(&self) -> &[Arc<T>] {
+        &self.bucket[..]
+    }
+
+    ///NW, NE, SE, SW
+    pub fn children(&self) -> Option<&[Box<QuadTree<S, T>>; 4]> {
+        self.children.as_ref()
+    }
+
+    ///Every object in the tree
+    pub fn items(&self) -> Vec<Arc<T>> {
+        let mut ret:Vec<Arc<T>> = Vec::new();
+        self.collect_all(&mut ret);
+        ret
+    }
+
+    fn collect_all(&self, ret: &mut Vec<Arc<T>>) {
+        ret.extend_from_slice(&self.bucket[..]);
         if let Some(ref c) = self.children {
-            for quad in c.into_iter() {
-                if let Some(items) = quad.get_in_radius(at) {
-                    ret.extend_from_slice(&items[..]);
+            for quad in c.iter() {
+                quad.collect_all(ret);
+            }
+        }
+    }
+
+    pub fn contents(&self) -> QuadTreeContents<S, T> {
+        QuadTreeContents {
+            volume: self.volume.clone(),
+            capacity: self.capacity,
+            max_depth: self.depth_limit,
+            items: self.items()
+        }
+    }
+
+    ///Rebuild a tree by inserting everything in `contents`
+    pub fn from_contents(contents: QuadTreeContents<S, T>) -> Result<QuadTree<S, T>, QuadTreeError> {
+        let mut qt = QuadTree::with_capacity(contents.volume, contents.capacity).with_max_depth(contents.max_depth);
+        for item in &contents.items {
+            qt.insert(item)?;
+        }
+        Ok(qt)
+    }
+
+    pub fn get_in_radius(&self, at: &Circle<S>) -> Option<Vec<Arc<T>>> {
+        self.query(&Query::Radius(at.clone()))
+    }
+
+    pub fn get_in_aabb(&self, area: &AABB2<S>) -> Option<Vec<Arc<T>>> {
+        self.query(&Query::Area(area.clone()))
+    }
+
+    pub fn get_on_line(&self, ray: &Line<S>) -> Option<Vec<Arc<T>>> {
+        self.query(&Query::Ray(ray.clone()))
+    }
+
+    ///Everything in the nodes the query touches, None if it misses the tree entirely
+    pub fn query(&self, q: &Query<S>) -> Option<Vec<Arc<T>>> {
+        if !q.touches(&self.volume) {
+            return None
+        }
+        let mut ret:Vec<Arc<T>> = Vec::new();
+        self.collect(q, &mut ret);
+        Some(ret)
+    }
+
+    fn collect(&self, q: &Query<S>, ret: &mut Vec<Arc<T>>) {
+        if let Some(ref c) = self.children {
+            for quad in c.iter() {
+                if q.touches(&quad.volume) {
+                    quad.collect(q, ret);
                 }
             }
         }
@@ -119,14 +402,64 @@ impl<'a, S, T> QuadTree<S, T> where T: SpacialIndex + Sized, S: SpacialKey {
                 ret.push(x.clone())
             }
         }
+    }
+
+}
+
+///`Send + Sync` with the `parallel` feature so batches can run on the rayon pool, anything without it
+#[cfg(feature = "parallel")]
+pub trait MaybeSync: Send + Sync {}
+#[cfg(feature = "parallel")]
+impl<X: Send + Sync> MaybeSync for X {}
+
+///`Send + Sync` with the `parallel` feature so batches can run on the rayon pool, anything without it
+#[cfg(not(feature = "parallel"))]
+pub trait MaybeSync {}
+#[cfg(not(feature = "parallel"))]
+impl<X> MaybeSync for X {}
+
+impl<S, T> QuadTree<S, T> where T: SpacialIndex + Sized + MaybeSync, S: SpacialKey + MaybeSync {
+    ///Run many queries at once, across the rayon pool with the `parallel` feature.
+    ///Results are in the same order as `queries`
+    pub fn query_batch(&self, queries: &[Query<S>]) -> Vec<Option<Vec<Arc<T>>>> {
+        #[cfg(feature = "parallel")]
+        let queries = queries.par_iter();
+        #[cfg(not(feature = "parallel"))]
+        let queries = queries.iter();
+        queries.map(|q| self.query(q)).collect()
+    }
 
-        return Some(ret);
+    ///Broadphase each query then keep the candidates `test` accepts, queries run like `query_batch`
+    pub fn narrowphase<F>(&self, queries: &[Query<S>], test: F) -> Vec<Vec<Arc<T>>>
+        where F: Fn(&Query<S>, &T) -> bool + MaybeSync {
+        #[cfg(feature = "parallel")]
+        let queries = queries.par_iter();
+        #[cfg(not(feature = "parallel"))]
+        let queries = queries.iter();
+        queries.map(|q| {
+            self.query(q).unwrap_or_default().into_iter().filter(|x| test(q, x)).collect()
+        }).collect()
     }
+}
 
+#[cfg(feature = "serde")]
+impl<S, T> ::serde::Serialize for QuadTree<S, T> where T: SpacialIndex + Sized + ::serde::Serialize, S: SpacialKey + ::serde::Serialize {
+    fn serialize<Z: ::serde::Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+        self.contents().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, S, T> ::serde::Deserialize<'de> for QuadTree<S, T> where T: SpacialIndex + Sized + ::serde::Deserialize<'de>, S: SpacialKey + ::serde::Deserialize<'de> {
+    fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let contents = QuadTreeContents::deserialize(deserializer)?;
+        QuadTree::from_contents(contents).map_err(::serde::de::Error::custom)
+    }
 }
 
 #[cfg(test)]
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 struct TestEntity {
     pub pos: Pnt2<f32>,
 }
@@ -148,14 +481,14 @@ impl SpacialIndex for TestEntity {
 #[test]
 fn quad_tree() {
     let mut qt = QuadTree::<f32, TestEntity>::with_capacity(AABB2::new(Pnt2::new(-10.0, 10.0), Pnt2::new(10.0, -10.0)), 2);
-    let mut entities = vec![Rc::new(TestEntity::new(Pnt2::new(-8.0, 8.0))), Rc::new(TestEntity::new(Pnt2::new(-9.0, 9.0))), Rc::new(TestEntity::new(Pnt2::new(2.0, 9.0)))];
+    let mut entities = vec![Arc::new(TestEntity::new(Pnt2::new(-8.0, 8.0))), Arc::new(TestEntity::new(Pnt2::new(-9.0, 9.0))), Arc::new(TestEntity::new(Pnt2::new(2.0, 9.0)))];
     for i in 0..entities.len()-1 {
-        qt.insert(&entities[i]);
+        qt.insert(&entities[i]).unwrap();
     }
 
     assert_eq!(qt.bucket.len(), 2);
 
-    qt.insert(&entities[2]);
+    qt.insert(&entities[2]).unwrap();
     assert_eq!(qt.bucket.len(), 0);
     assert_eq!(qt.children.is_some(), true);
     assert_eq!(qt.children.as_ref().unwrap()[1].bucket.len(), 1);
@@ -163,12 +496,140 @@ fn quad_tree() {
     assert_eq!(qt.children.as_ref().unwrap()[1].volume, AABB2::new(Pnt2::new(0.0, 10.0), Pnt2::new(10.0, 0.0)));
 
     let old_pos = entities[2].get_position();
-    Rc::make_mut(&mut entities[2]).pos.x = -2.0;
-    qt.update(&old_pos, &entities[2]);
+    Arc::make_mut(&mut entities[2]).pos.x = -2.0;
+    qt.update(&old_pos, &entities[2]).unwrap();
 
     assert_eq!(qt.children.as_ref().unwrap()[0].children.as_ref().unwrap()[1].bucket.len(), 1);
     assert_eq!(qt.children.as_ref().unwrap()[0].children.as_ref().unwrap()[1].bucket[0], entities[2]);
 
-    assert_eq!(qt.get_in_radius(&Circle::new(Pnt2::new(-13.0, -13.0), 4.0)), Some(entities.clone().into_iter().take(2).collect::<Vec<Rc<TestEntity>>>()));
+    assert_eq!(qt.get_in_radius(&Circle::new(Pnt2::new(-13.0, -13.0), 4.0)), Some(entities.clone().into_iter().take(2).collect::<Vec<Arc<TestEntity>>>()));
     assert_eq!(qt.get_in_radius(&Circle::new(Pnt2::new(-13.0, -13.0), 12.0)), Some(entities));
 }
+
+#[test]
+fn quad_tree_batch() {
+    let mut qt = QuadTree::<f32, TestEntity>::with_capacity(AABB2::new(Pnt2::new(-10.0, 10.0), Pnt2::new(10.0, -10.0)), 1);
+    let entities = vec![Arc::new(TestEntity::new(Pnt2::new(-5.0, 5.0))), Arc::new(TestEntity::new(Pnt2::new(5.0, 5.0))), Arc::new(TestEntity::new(Pnt2::new(5.0, -5.0)))];
+    for e in &entities {
+        qt.insert(e).unwrap();
+    }
+
+    let queries = vec![Query::Area(AABB2::new(Pnt2::new(1.0, 9.0), Pnt2::new(9.0, 1.0))),
+                       Query::Ray(Line::new(Pnt2::new(-9.0, -1.0), Pnt2::new(9.0, -9.0))),
+                       Query::Radius(Circle::new(Pnt2::new(-30.0, -30.0), 1.0))];
+
+    assert_eq!(qt.query_batch(&queries), vec![Some(vec![entities[1].clone()]), Some(vec![entities[2].clone()]), None]);
+
+    let near = qt.narrowphase(&[Query::Area(AABB2::new(Pnt2::new(-9.0, 9.0), Pnt2::new(9.0, -9.0)))], |_, e| e.pos.y > 0.0);
+    assert_eq!(near, vec![vec![entities[0].clone(), entities[1].clone()]]);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn quad_tree_serde() {
+    let mut qt = QuadTree::<f32, TestEntity>::with_capacity(AABB2::new(Pnt2::new(-10.0, 10.0), Pnt2::new(10.0, -10.0)), 1);
+    for p in [Pnt2::new(-5.0, 5.0), Pnt2::new(5.0, 5.0), Pnt2::new(5.0, -5.0)].iter() {
+        qt.insert(&Arc::new(TestEntity::new(*p))).unwrap();
+    }
+
+    let json = ::serde_json::to_string(&qt).unwrap();
+    let back: QuadTree<f32, TestEntity> = ::serde_json::from_str(&json).unwrap();
+    assert_eq!(back.items(), qt.items());
+    assert_eq!(back.children.as_ref().unwrap()[1].bucket, qt.children.as_ref().unwrap()[1].bucket);
+
+    let json = ::serde_json::to_string(&qt.contents()).unwrap();
+    let back = QuadTree::from_contents(::serde_json::from_str::<QuadTreeContents<f32, TestEntity>>(&json).unwrap()).unwrap();
+    assert_eq!(back.items(), qt.items());
+
+    //the nodes come from the objects, never from the file
+    let edited = json.replace("\"capacity\":1", "\"capacity\":8");
+    let back: QuadTree<f32, TestEntity> = ::serde_json::from_str(&edited).unwrap();
+    assert!(back.children.is_none());
+    assert_eq!(back.bucket.len(), 3);
+
+    //too big for an f32, so it reads as infinity and can't be placed
+    let huge = r#"{"volume":{"tl":[-10.0,10.0],"br":[10.0,-10.0]},"capacity":1,"max_depth":4,"items":[{"pos":[1e39,0.0]}]}"#;
+    let err = ::serde_json::from_str::<QuadTree<f32, TestEntity>>(huge).unwrap_err();
+    assert_eq!(err.to_string(), QuadTreeError::InvalidPosition.to_string());
+}
+
+#[test]
+fn quad_tree_growth() {
+    let mut qt = QuadTree::<f32, TestEntity>::with_capacity(AABB2::new(Pnt2::new(-10.0, 10.0), Pnt2::new(10.0, -10.0)), 2);
+    let inside = Arc::new(TestEntity::new(Pnt2::new(-5.0, 5.0)));
+    let far = Arc::new(TestEntity::new(Pnt2::new(50.0, -50.0)));
+    qt.insert(&inside).unwrap();
+    qt.insert(&far).unwrap();
+
+    //grew right and down twice, the original root ends up NW of NW
+    assert_eq!(qt.volume, AABB2::new(Pnt2::new(-10.0, 10.0), Pnt2::new(70.0, -70.0)));
+    assert_eq!(qt.children.as_ref().unwrap()[0].children.as_ref().unwrap()[0].volume, AABB2::new(Pnt2::new(-10.0, 10.0), Pnt2::new(10.0, -10.0)));
+    assert_eq!(qt.items().len(), 2);
+    assert_eq!(qt.get_in_radius(&Circle::new(Pnt2::new(50.0, -50.0), 1.0)), Some(vec![far.clone()]));
+
+    let mut fixed = QuadTree::<f32, TestEntity>::with_capacity(AABB2::new(Pnt2::new(-10.0, 10.0), Pnt2::new(10.0, -10.0)), 2).growable(false);
+    assert_eq!(fixed.insert(&far), Err(QuadTreeError::OutOfBounds));
+    assert_eq!(fixed.insert(&Arc::new(TestEntity::new(Pnt2::new(f32::NAN, 0.0)))), Err(QuadTreeError::InvalidPosition));
+    assert_eq!(fixed.update(&Pnt2::new(1.0, 1.0), &inside), Err(QuadTreeError::NotFound));
+}
+
+#[test]
+fn quad_tree_max_depth() {
+    let mut qt = QuadTree::<f32, TestEntity>::with_capacity(AABB2::new(Pnt2::new(-10.0, 10.0), Pnt2::new(10.0, -10.0)), 1).with_max_depth(3);
+    let same = Arc::new(TestEntity::new(Pnt2::new(9.0, 9.0)));
+    for _ in 0..10 {
+        qt.insert(&same).unwrap();
+    }
+
+    //everything piles up in the overflow bucket three levels down
+    let mut node = &qt;
+    for _ in 0..3 {
+        node = &node.children.as_ref().unwrap()[1];
+    }
+    assert!(node.children.is_none());
+    assert_eq!(node.bucket.len(), 10);
+
+    //lowering the limit later reaches the children that already exist
+    let mut qt = QuadTree::<f32, TestEntity>::with_capacity(AABB2::new(Pnt2::new(-10.0, 10.0), Pnt2::new(10.0, -10.0)), 1);
+    qt.insert(&Arc::new(TestEntity::new(Pnt2::new(-5.0, 5.0)))).unwrap();
+    qt.insert(&same).unwrap();
+    let mut qt = qt.with_max_depth(1);
+    for _ in 0..5 {
+        qt.insert(&same).unwrap();
+    }
+    assert_eq!(qt.stats().depth, 1);
+    assert_eq!(qt.children.as_ref().unwrap()[1].bucket.len(), 6);
+}
+
+#[test]
+fn quad_tree_collapse() {
+    let mut qt = QuadTree::<f32, TestEntity>::with_capacity(AABB2::new(Pnt2::new(-10.0, 10.0), Pnt2::new(10.0, -10.0)), 2);
+    let entities = vec![Arc::new(TestEntity::new(Pnt2::new(-5.0, 5.0))), Arc::new(TestEntity::new(Pnt2::new(5.0, 5.0))), Arc::new(TestEntity::new(Pnt2::new(5.0, -5.0)))];
+    for e in &entities {
+        qt.insert(e).unwrap();
+    }
+
+    assert_eq!(qt.stats(), QuadTreeStats { depth: 1, nodes: 5, leaves: 4, items: 3, items_per_level: vec![0, 3] });
+
+    //two left isn't below capacity yet
+    qt.remove_key(&Pnt2::new(5.0, -5.0)).unwrap();
+    assert!(qt.children.is_some());
+
+    qt.remove_key(&Pnt2::new(5.0, 5.0)).unwrap();
+    assert!(qt.children.is_none());
+    assert_eq!(qt.bucket, vec![entities[0].clone()]);
+    assert_eq!(qt.stats(), QuadTreeStats { depth: 0, nodes: 1, leaves: 1, items: 1, items_per_level: vec![1] });
+
+    //growing leaves empty quadrants around, optimize folds them away
+    let mut grown = QuadTree::<f32, TestEntity>::with_capacity(AABB2::new(Pnt2::new(-10.0, 10.0), Pnt2::new(10.0, -10.0)), 2);
+    grown.insert(&Arc::new(TestEntity::new(Pnt2::new(25.0, -25.0)))).unwrap();
+    assert_eq!(grown.stats().nodes, 5);
+    grown.optimize();
+    assert_eq!(grown.stats(), QuadTreeStats { depth: 0, nodes: 1, leaves: 1, items: 1, items_per_level: vec![1] });
+
+    for e in &entities {
+        qt.insert(e).unwrap();
+    }
+    assert!(qt.rebuild().is_empty());
+    assert_eq!(qt.stats().items, 4);
+}
@@ -0,0 +1,98 @@
+use glium::{Blend, Depth, DepthTest, DrawParameters, Program, Surface, VertexBuffer};
+use glium::backend::Facade;
+use glium::index::{NoIndices, PrimitiveType};
+use super::debug::{DebugLines, DebugVertex};
+
+implement_vertex!(DebugVertex, position, color);
+
+const VERTEX_SHADER: &str = r#"
+    #version 140
+
+    uniform mat4 matrix;
+    in vec2 position;
+    in vec4 color;
+    out vec4 v_color;
+
+    void main() {
+        v_color = color;
+        gl_Position = matrix * vec4(position, 0.0, 1.0);
+    }
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+    #version 140
+
+    in vec4 v_color;
+    out vec4 f_color;
+
+    void main() {
+        f_color = v_color;
+    }
+"#;
+
+#[derive(Debug, Error)]
+pub enum DebugRenderError {
+    #[error("Failed to build debug shader: {source}")]
+    ProgramError {
+        #[from]
+        source: glium::ProgramCreationError
+    },
+    #[error("Failed to upload debug lines: {source}")]
+    BufferError {
+        #[from]
+        source: glium::vertex::BufferCreationError
+    },
+    #[error("Failed to draw debug lines: {source}")]
+    DrawError {
+        #[from]
+        source: glium::DrawError
+    }
+}
+
+///Orthographic projection for `DebugRenderer::draw`, maps the given world rect to the screen
+pub fn ortho(left: f32, right: f32, bottom: f32, top: f32) -> [[f32; 4]; 4] {
+    let w = right - left;
+    let h = top - bottom;
+    [
+        [2.0 / w, 0.0, 0.0, 0.0],
+        [0.0, 2.0 / h, 0.0, 0.0],
+        [0.0, 0.0, -1.0, 0.0],
+        [-(right + left) / w, -(top + bottom) / h, 0.0, 1.0],
+    ]
+}
+
+///Draws `DebugLines` as a line list on top of whatever is in the frame
+pub struct DebugRenderer {
+    program: Program
+}
+
+impl DebugRenderer {
+    pub fn new<F: Facade>(display: &F) -> Result<DebugRenderer, DebugRenderError> {
+        Ok(DebugRenderer {
+            program: Program::from_source(display, VERTEX_SHADER, FRAGMENT_SHADER, None)?
+        })
+    }
+
+    pub fn draw<F: Facade, T: Surface>(&self, display: &F, target: &mut T, lines: &DebugLines, matrix: [[f32; 4]; 4]) -> Result<(), DebugRenderError> {
+        if lines.is_empty() {
+            return Ok(())
+        }
+
+        let vb = VertexBuffer::new(display, lines.vertices())?;
+        let uniforms = uniform! {
+            matrix: matrix
+        };
+        //an overlay, so colours keep their alpha and nothing in the depth buffer hides the lines
+        let params = DrawParameters {
+            blend: Blend::alpha_blending(),
+            depth: Depth {
+                test: DepthTest::Overwrite,
+                write: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        target.draw(&vb, &NoIndices(PrimitiveType::LinesList), &self.program, &uniforms, &params)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,467 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::str::FromStr;
+use super::{parse_value, Config, ConfigError, KeySchema, Layer, Value, ValueKind};
+
+#[derive(Debug, Error)]
+pub enum ConsoleError {
+    #[error("Unknown command \"{0}\"")]
+    UnknownCommand(String),
+    #[error("Unknown cvar \"{0}\"")]
+    UnknownCvar(String),
+    #[error("Unterminated quote")]
+    UnterminatedQuote,
+    #[error("Usage: {0}")]
+    Usage(String),
+    #[error("Bad argument {index} to {command}: {message}")]
+    InvalidArgument {
+        command: String,
+        index: usize,
+        message: String
+    },
+    #[error("Can't set {name}: {message}")]
+    InvalidValue {
+        name: String,
+        message: String
+    },
+    #[error("{0}")]
+    Failed(String),
+    #[error("{source}")]
+    ConfigError {
+        #[from]
+        source: ConfigError
+    }
+}
+
+///! A console command, gets the config and the words after its name and returns what to print
+pub type CommandFn = dyn FnMut(&mut Config, &Args) -> Result<String, ConsoleError>;
+
+const BUILTINS: [&str; 2] = ["help", "reset"];
+const HISTORY_SIZE: usize = 100;
+
+///! The arguments a command was run with
+pub struct Args<'a> {
+    command: &'a str,
+    usage: &'a str,
+    words: &'a [String]
+}
+
+impl<'a> Args<'a> {
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn word(&self, index: usize) -> Option<&str> {
+        self.words.get(index).map(|w| &w[..])
+    }
+
+    ///! Argument index parsed as T, a missing argument is a usage error
+    pub fn get<T>(&self, index: usize) -> Result<T, ConsoleError> where T: FromStr, T::Err: Display {
+        let word = self.word(index).ok_or_else(|| ConsoleError::Usage(self.usage.to_string()))?;
+        word.parse().map_err(|e: T::Err| ConsoleError::InvalidArgument {
+            command: self.command.to_string(),
+            index,
+            message: e.to_string()
+        })
+    }
+
+    ///! Everything from index on, joined with spaces
+    pub fn rest(&self, index: usize) -> String {
+        self.words.get(index..).map(|w| w.join(" ")).unwrap_or_default()
+    }
+}
+
+struct Command {
+    usage: String,
+    f: Box<CommandFn>
+}
+
+///! A console variable backed by a config key, archived ones are saved with the user config
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cvar {
+    key: String,
+    schema: KeySchema,
+    archive: bool
+}
+
+impl Cvar {
+    pub fn new(key: &str, schema: KeySchema) -> Cvar {
+        Cvar {
+            key: key.to_string(),
+            schema,
+            archive: false
+        }
+    }
+
+    pub fn with_archive(mut self) -> Self {
+        self.archive = true;
+        self
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn schema(&self) -> &KeySchema {
+        &self.schema
+    }
+
+    pub fn is_archived(&self) -> bool {
+        self.archive
+    }
+
+    //strings take the text as is, everything else is read as toml
+    fn parse(&self, text: &str) -> Value {
+        match self.schema.kind() {
+            ValueKind::String => Value::String(text.to_string()),
+            _ => parse_value(text)
+        }
+    }
+}
+
+//statements split on `;`, each split into words, double quotes group words and `\` escapes inside them
+fn split(line: &str) -> Result<Vec<Vec<String>>, ConsoleError> {
+    let mut statements = Vec::new();
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let w = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => w.push(chars.next().ok_or(ConsoleError::UnterminatedQuote)?),
+                        Some(c) => w.push(c),
+                        None => return Err(ConsoleError::UnterminatedQuote)
+                    }
+                }
+            },
+            ';' => {
+                words.extend(word.take());
+                if !words.is_empty() {
+                    statements.push(std::mem::take(&mut words));
+                }
+            },
+            c if c.is_whitespace() => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c)
+        }
+    }
+    words.extend(word.take());
+    if !words.is_empty() {
+        statements.push(words);
+    }
+    Ok(statements)
+}
+
+fn common_prefix(words: &[String]) -> Option<String> {
+    let first = words.first()?;
+    let len = words.iter().fold(first.len(), |len, w| {
+        first.char_indices().zip(w.chars())
+            .take_while(|&((i, a), b)| i < len && a == b)
+            .last().map(|((i, a), _)| i + a.len_utf8()).unwrap_or(0)
+    });
+    Some(first[..len].to_string())
+}
+
+///! A headless Quake style console, feed it lines with `execute` and draw what it returns however you like.
+///! `name` prints a cvar, `name value` sets it, `reset name` puts it back and `help` lists everything.
+pub struct Console {
+    commands: BTreeMap<String, Command>,
+    cvars: BTreeMap<String, Cvar>,
+    history: Vec<String>,
+    history_size: usize,
+    cursor: Option<usize>
+}
+
+impl Default for Console {
+    fn default() -> Console {
+        Console::new()
+    }
+}
+
+impl Console {
+    pub fn new() -> Console {
+        Console {
+            commands: BTreeMap::new(),
+            cvars: BTreeMap::new(),
+            history: Vec::new(),
+            history_size: HISTORY_SIZE,
+            cursor: None
+        }
+    }
+
+    ///! usage is shown by `help` and when arguments are missing, like `give <item> [count]`
+    pub fn with_command(mut self, name: &str, usage: &str, f: Box<CommandFn>) -> Self {
+        self.commands.insert(name.to_string(), Command { usage: usage.to_string(), f });
+        self
+    }
+
+    pub fn with_cvar(mut self, name: &str, cvar: Cvar) -> Self {
+        self.cvars.insert(name.to_string(), cvar);
+        self
+    }
+
+    ///! How many lines of history to keep, the oldest go first
+    pub fn with_history_size(mut self, size: usize) -> Self {
+        self.history_size = size;
+        self
+    }
+
+    pub fn cvar(&self, name: &str) -> Option<&Cvar> {
+        self.cvars.get(name)
+    }
+
+    ///! Run every `;` separated statement in line, returns their output joined by newlines.
+    ///! Stops at the first statement that fails.
+    pub fn execute(&mut self, config: &mut Config, line: &str) -> Result<String, ConsoleError> {
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(String::new())
+        }
+        self.remember(line);
+
+        let mut out = Vec::new();
+        for words in split(line)? {
+            let text = self.run(config, &words)?;
+            if !text.is_empty() {
+                out.push(text);
+            }
+        }
+        Ok(out.join("\n"))
+    }
+
+    fn run(&mut self, config: &mut Config, words: &[String]) -> Result<String, ConsoleError> {
+        let (name, words) = words.split_first().expect("split never makes empty statements");
+        match &name[..] {
+            "help" => return Ok(self.help(config)),
+            "reset" => return self.reset(config, words),
+            _ => {}
+        }
+
+        if let Some(command) = self.commands.get_mut(name) {
+            let args = Args { command: name, usage: &command.usage, words };
+            return (command.f)(config, &args)
+        }
+
+        let cvar = self.cvars.get(name).ok_or_else(|| ConsoleError::UnknownCommand(name.clone()))?;
+        if words.is_empty() {
+            return Ok(Console::show(name, cvar, config))
+        }
+
+        let value = cvar.parse(&words.join(" "));
+        cvar.schema.check(&value).map_err(|message| ConsoleError::InvalidValue { name: name.clone(), message })?;
+        config.set_in(Layer::Console, &cvar.key, value.clone())?;
+        if cvar.archive {
+            config.set(&cvar.key, value)?;
+        }
+        Ok(String::new())
+    }
+
+    fn show(name: &str, cvar: &Cvar, config: &Config) -> String {
+        match config.value(&cvar.key) {
+            Some(v) => format!("{} = {}", name, v),
+            None => format!("{} isn't set", name)
+        }
+    }
+
+    fn reset(&mut self, config: &mut Config, words: &[String]) -> Result<String, ConsoleError> {
+        let name = words.first().ok_or_else(|| ConsoleError::Usage("reset <cvar>".to_string()))?;
+        let cvar = self.cvars.get(name).ok_or_else(|| ConsoleError::UnknownCvar(name.clone()))?;
+        config.remove_in(Layer::Console, &cvar.key);
+        if cvar.archive {
+            config.remove(&cvar.key);
+        }
+        Ok(Console::show(name, cvar, config))
+    }
+
+    fn help(&self, config: &Config) -> String {
+        let mut lines = vec!["help".to_string(), "reset <cvar>".to_string()];
+        lines.extend(self.commands.values().map(|c| c.usage.clone()));
+        for (name, cvar) in &self.cvars {
+            let mut line = Console::show(name, cvar, config);
+            if !cvar.schema.description().is_empty() {
+                line = format!("{} - {}", line, cvar.schema.description());
+            }
+            lines.push(line);
+        }
+        lines.join("\n")
+    }
+
+    //words that can follow what's already typed of a statement
+    fn candidates(&self, words: &[&str]) -> Vec<String> {
+        match words.len() {
+            0 | 1 => BUILTINS.iter().map(|b| b.to_string())
+                .chain(self.commands.keys().cloned())
+                .chain(self.cvars.keys().cloned())
+                .collect(),
+            2 if words[0] == "reset" => self.cvars.keys().cloned().collect(),
+            2 => match self.cvars.get(words[0]) {
+                Some(cvar) if cvar.schema.kind() == ValueKind::Boolean => vec!["false".to_string(), "true".to_string()],
+                Some(cvar) => cvar.schema.allowed().iter()
+                    .map(|v| v.as_str().map(|s| s.to_string()).unwrap_or_else(|| v.to_string()))
+                    .collect(),
+                None => Vec::new()
+            },
+            _ => Vec::new()
+        }
+    }
+
+    ///! Every way to finish the last word of line, as whole lines
+    pub fn completions(&self, line: &str) -> Vec<String> {
+        let start = line.rfind(';').map(|i| i + 1).unwrap_or(0);
+        let mut words:Vec<&str> = line[start..].split_whitespace().collect();
+        if line.ends_with(char::is_whitespace) || words.is_empty() {
+            words.push("");
+        }
+        let partial = words[words.len() - 1];
+        let before = &line[..line.len() - partial.len()];
+
+        let mut found:Vec<String> = self.candidates(&words).into_iter()
+            .filter(|c| c.starts_with(partial))
+            .map(|c| format!("{}{}", before, c))
+            .collect();
+        found.sort();
+        found.dedup();
+        found
+    }
+
+    ///! What tab does: line extended as far as every completion agrees
+    pub fn complete(&self, line: &str) -> String {
+        let found = self.completions(line);
+        match found.len() {
+            1 => format!("{} ", found[0]),
+            _ => common_prefix(&found).filter(|p| p.len() > line.len()).unwrap_or_else(|| line.to_string())
+        }
+    }
+
+    fn remember(&mut self, line: &str) {
+        self.cursor = None;
+        if self.history.last().map(|l| l == line).unwrap_or(false) {
+            return
+        }
+        self.history.push(line.to_string());
+        if self.history.len() > self.history_size {
+            let extra = self.history.len() - self.history_size;
+            self.history.drain(..extra);
+        }
+    }
+
+    ///! Oldest first
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    ///! Step back through history, like pressing up
+    pub fn older(&mut self) -> Option<&str> {
+        let index = match self.cursor {
+            None => self.history.len().checked_sub(1)?,
+            Some(i) => i.saturating_sub(1)
+        };
+        self.cursor = Some(index);
+        self.history.get(index).map(|l| &l[..])
+    }
+
+    ///! Step forward through history, None once back at the empty line
+    pub fn newer(&mut self) -> Option<&str> {
+        let index = self.cursor? + 1;
+        if index >= self.history.len() {
+            self.cursor = None;
+            return None
+        }
+        self.cursor = Some(index);
+        self.history.get(index).map(|l| &l[..])
+    }
+}
+
+#[test]
+fn console() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let path = std::env::temp_dir().join("pipewrench_console.toml").to_string_lossy().into_owned();
+    let given = Rc::new(RefCell::new(Vec::new()));
+    let inventory = given.clone();
+    let mut console = Console::new()
+        .with_cvar("r_width", Cvar::new("window.width", KeySchema::integer().with_range(320.0, 7680.0).with_description("Window width")).with_archive())
+        .with_cvar("r_mode", Cvar::new("window.mode", KeySchema::string().with_allowed(vec!["windowed", "fullscreen"])))
+        .with_cvar("r_vsync", Cvar::new("window.vsync", KeySchema::boolean()))
+        .with_command("give", "give <item> [count]", Box::new(move |_, args| {
+            let count = if args.len() > 1 { args.get::<u32>(1)? } else { 1 };
+            inventory.borrow_mut().push((args.get::<String>(0)?, count));
+            Ok(format!("gave {} {}", count, args.rest(0)))
+        }));
+    let mut config = Config::new()
+        .with_toml(Layer::Defaults, "[window]\nwidth = 1280\nmode = \"windowed\"\nvsync = true").unwrap()
+        .with_args(vec!["--set", "window.width=1920"]).unwrap();
+
+    assert_eq!(console.execute(&mut config, "r_width").unwrap(), "r_width = 1920");
+    assert_eq!(console.execute(&mut config, "r_width 800; r_mode fullscreen ;r_vsync false").unwrap(), "");
+    assert_eq!(config.value_int("window.width"), Some(800));
+    assert_eq!(config.value_string("window.mode"), Some("fullscreen"));
+    assert_eq!(config.value_bool("window.vsync"), Some(false));
+    assert_eq!(console.execute(&mut config, "give \"big \\\"gun\\\"\" 2").unwrap(), "gave 2 big \"gun\" 2");
+    assert_eq!(*given.borrow(), vec![("big \"gun\"".to_string(), 2)]);
+
+    //only the archived cvar reaches the user layer and gets saved
+    config.save(&path).unwrap();
+    let saved = Config::from_file(&path).unwrap();
+    assert_eq!(saved.value_int("window.width"), Some(800));
+    assert!(!saved.contains("window.mode"));
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(console.execute(&mut config, "reset r_width").unwrap(), "r_width = 1920");
+    assert_eq!(console.execute(&mut config, "reset r_mode").unwrap(), "r_mode = \"windowed\"");
+
+    match console.execute(&mut config, "r_width 10") {
+        Err(ConsoleError::InvalidValue { ref name, .. }) if name == "r_width" => {},
+        other => panic!("{:?}", other)
+    }
+    match console.execute(&mut config, "r_mode borderless") {
+        Err(ConsoleError::InvalidValue { .. }) => {},
+        other => panic!("{:?}", other)
+    }
+    match console.execute(&mut config, "give") {
+        Err(ConsoleError::Usage(ref u)) if u == "give <item> [count]" => {},
+        other => panic!("{:?}", other)
+    }
+    match console.execute(&mut config, "give gun lots") {
+        Err(ConsoleError::InvalidArgument { index: 1, .. }) => {},
+        other => panic!("{:?}", other)
+    }
+    match console.execute(&mut config, "noclip") {
+        Err(ConsoleError::UnknownCommand(ref c)) if c == "noclip" => {},
+        other => panic!("{:?}", other)
+    }
+    match console.execute(&mut config, "give \"gun") {
+        Err(ConsoleError::UnterminatedQuote) => {},
+        other => panic!("{:?}", other)
+    }
+    assert!(console.execute(&mut config, "help").unwrap().contains("r_width = 1920 - Window width"));
+
+    assert_eq!(console.completions("r_"), vec!["r_mode", "r_vsync", "r_width"]);
+    assert_eq!(console.complete("r_w"), "r_width ");
+    assert_eq!(console.complete("r_mode f"), "r_mode fullscreen ");
+    assert_eq!(console.completions("r_vsync "), vec!["r_vsync false", "r_vsync true"]);
+    assert_eq!(console.complete("give gun; res"), "give gun; reset ");
+    assert_eq!(console.complete("x"), "x");
+
+    //history skips repeats and walks both ways
+    console.execute(&mut config, "help").unwrap();
+    assert_eq!(console.history().len(), 12);
+    assert_eq!(console.older(), Some("help"));
+    assert_eq!(console.older(), Some("give \"gun"));
+    assert_eq!(console.newer(), Some("help"));
+    assert_eq!(console.newer(), None);
+
+    let mut short = Console::new().with_history_size(2);
+    for line in &["a", "b", "c"] {
+        let _ = short.execute(&mut config, line);
+    }
+    assert_eq!(short.history(), &["b".to_string(), "c".to_string()][..]);
+}
@@ -0,0 +1,1107 @@
+use std::fs::{self, File, OpenOptions};
+use std::fmt;
+use std::path::Path;
+use std::io::{Read, Write};
+use std::time::SystemTime;
+use input::{Binding, BindingState};
+use dirs::AppDirs;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use toml::map::Entry;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+pub use toml::{Value, Table};
+pub use toml::value::{Array, Datetime};
+
+mod schema;
+mod console;
+
+pub use self::schema::*;
+pub use self::console::*;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Key is empty")]
+    EmptyKey,
+    #[error("Error parsing config at line {line}, column {column}: {message}")]
+    TomlParseError {
+        line: usize,
+        column: usize,
+        message: String
+    },
+    #[error("Config has no key \"{0}\"")]
+    MissingKey(String),
+    #[error("Unknown key \"{key}\"{}", suggestion.as_ref().map(|s| format!(", did you mean \"{}\"?", s)).unwrap_or_default())]
+    UnknownKey {
+        key: String,
+        suggestion: Option<String>
+    },
+    #[error("Override \"{0}\" isn't key=value")]
+    InvalidOverride(String),
+    #[error("Invalid value for \"{key}\"{}: {message}", line.map(|l| format!(" at line {}", l)).unwrap_or_default())]
+    InvalidValue {
+        key: String,
+        line: Option<usize>,
+        message: String
+    },
+    #[error("IOError: {source}")]
+    IOError {
+        #[from]
+        source: std::io::Error
+    }
+}
+
+//1 based line and column of a byte offset
+fn position(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map(|i| i + 1).unwrap_or(0) + 1;
+    (line, column)
+}
+
+///! Where a config value came from, later layers override earlier ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Layer {
+    ///! Built into the game
+    Defaults,
+    ///! The config shipped with the game
+    Game,
+    ///! The player's own config, `set` and `save` only touch this one
+    User,
+    ///! `PIPEWRENCH_` environment variables
+    Env,
+    ///! `--set key=value` arguments
+    Args,
+    ///! Console variables changed this session, gone on restart unless archived
+    Console
+}
+
+const ENV_PREFIX: &str = "PIPEWRENCH_";
+const USER_FILE: &str = "config.toml";
+
+///! Called with the changed key and its new value, None when it was removed
+pub type ChangeHandler = dyn Fn(&str, Option<&Value>);
+
+fn parse_table(conf: &str) -> Result<Table, ConfigError> {
+    conf.parse::<Table>().map_err(|e| {
+        let (line, column) = position(conf, e.span().map(|s| s.start).unwrap_or(0));
+        ConfigError::TomlParseError {
+            line,
+            column,
+            message: e.message().trim().to_string()
+        }
+    })
+}
+
+//override values are toml when they parse as toml and plain strings otherwise
+fn parse_value(text: &str) -> Value {
+    match format!("v = {}", text).parse::<Table>() {
+        Ok(mut t) => t.remove("v").unwrap(),
+        Err(_) => Value::String(text.to_string())
+    }
+}
+
+///! A binding or an array of them as `Config::bindings` reads them, state is used when one doesn't give its own
+pub fn parse_bindings(value: &Value, state: BindingState) -> Result<Vec<Binding>, String> {
+    let texts:Vec<&Value> = match *value {
+        Value::Array(ref a) => a.iter().collect(),
+        ref v => vec![v]
+    };
+    texts.into_iter().map(|text| {
+        let text = text.as_str().ok_or_else(|| format!("expected a binding, found {}", text.type_str()))?;
+        Binding::parse(text, state.clone()).map_err(|e| e.to_string())
+    }).collect()
+}
+
+fn read_file(path: &str) -> Result<String, ConfigError> {
+    let mut f = File::open(&Path::new(path))?;
+    let mut conf:String = "".to_string();
+    f.read_to_string(&mut conf)?;
+    Ok(conf)
+}
+
+fn merge(into: &mut Table, from: &Table) {
+    for (k, v) in from {
+        if let (Some(&mut Value::Table(ref mut a)), Value::Table(b)) = (into.get_mut(k), v) {
+            merge(a, b);
+            continue
+        }
+        into.insert(k.clone(), v.clone());
+    }
+}
+
+fn find<'a>(table: &'a Table, name: &str) -> Option<&'a Value> {
+    let mut keys = name.split('.');
+    let first = table.get(keys.next()?)?;
+    keys.try_fold(first, |v, k| v.get(k))
+}
+
+//set name to val in table, creating tables along the way
+fn insert(table: &mut Table, name: &str, val: Value) -> Result<(), ConfigError> {
+    if name.len() == 0 { return Err(ConfigError::EmptyKey) }
+    let mut curr = table;
+
+    let keys:Vec<&str> = name.split('.').collect();
+
+    for k in keys.clone().into_iter().take(keys.len()-1) {
+        let tmp = curr;
+        if k.len() == 0 { return Err(ConfigError::EmptyKey) }
+        curr = match tmp.entry(k.to_string()) {
+            Entry::Vacant(slot) => match slot.insert(Value::Table(Table::new())) {
+                &mut Value::Table(ref mut t) => t,
+                _ => unreachable!()
+            },
+            Entry::Occupied(slot) => {
+                let v = slot.into_mut();
+                match v {
+                    &mut Value::Table(ref mut t) => t,
+                    _ => {
+                        *v = Value::Table(Table::new());
+                        match v {
+                            &mut Value::Table(ref mut t) => t,
+                            _ => unreachable!()
+                        }
+                    },
+                }
+            },
+        };
+    }
+    if keys[keys.len()-1].len() == 0 {
+        return Err(ConfigError::EmptyKey)
+    }
+
+    curr.insert(keys[keys.len()-1].to_string(), val);
+    Ok(())
+}
+
+//every non table value under table by its dotted key
+fn leaves<'a>(prefix: &str, table: &'a Table, out: &mut BTreeMap<String, &'a Value>) {
+    for (k, v) in table {
+        let key = if prefix.is_empty() { k.clone() } else { format!("{}.{}", prefix, k) };
+        match *v {
+            Value::Table(ref t) => leaves(&key, t, out),
+            _ => { out.insert(key, v); }
+        }
+    }
+}
+
+//keys whose value differs between two tables
+fn changed_keys(old: &Table, new: &Table) -> Vec<String> {
+    let (mut before, mut after) = (BTreeMap::new(), BTreeMap::new());
+    leaves("", old, &mut before);
+    leaves("", new, &mut after);
+    let mut keys:Vec<String> = before.iter()
+        .filter(|&(k, v)| after.get(k) != Some(v))
+        .map(|(k, _)| k.clone())
+        .collect();
+    keys.extend(after.keys().filter(|k| !before.contains_key(*k)).cloned());
+    keys.sort();
+    keys
+}
+
+//`*` matches any one key, a trailing `*` anything below, and a pattern matches everything under it
+fn matches(pattern: &str, key: &str) -> bool {
+    let mut pattern = pattern.split('.').peekable();
+    let mut key = key.split('.');
+    loop {
+        match (pattern.next(), key.next()) {
+            (Some("*"), Some(_)) if pattern.peek().is_none() => return true,
+            (Some("*"), Some(_)) => {},
+            (Some(p), Some(k)) if p == k => {},
+            (None, _) => return true,
+            _ => return false
+        }
+    }
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+//take name out of table, leaving emptied tables behind
+fn take(table: &mut Table, name: &str) -> Option<Value> {
+    match name.find('.') {
+        Some(i) => match table.get_mut(&name[..i]) {
+            Some(&mut Value::Table(ref mut t)) => take(t, &name[i + 1..]),
+            _ => None
+        },
+        None => table.remove(name)
+    }
+}
+
+fn edit_value(val: &Value) -> toml_edit::Value {
+    val.to_string().parse().expect("toml values always print as valid toml")
+}
+
+//make doc hold exactly values, leaving untouched keys, comments and order alone
+fn sync(doc: &mut toml_edit::Table, values: &Table) {
+    let gone:Vec<String> = doc.iter().map(|(k, _)| k.to_string()).filter(|k| !values.contains_key(k)).collect();
+    for k in gone {
+        doc.remove(&k);
+    }
+
+    for (k, v) in values {
+        if let Value::Table(ref t) = *v {
+            let is_table = doc.get(k).map(|i| i.is_table()).unwrap_or(false);
+            let is_value = doc.get(k).map(|i| i.is_value()).unwrap_or(false);
+            if !is_value {
+                if !is_table {
+                    doc.insert(k, toml_edit::Item::Table(toml_edit::Table::new()));
+                }
+                sync(doc[&k[..]].as_table_mut().unwrap(), t);
+                continue
+            }
+        }
+
+        //[[arrays of tables]] are edited in place as long as every element is still a table
+        if let Value::Array(ref items) = *v {
+            if let Some(tables) = doc.get_mut(k).and_then(|i| i.as_array_of_tables_mut()) {
+                if !items.is_empty() && items.iter().all(|i| i.is_table()) {
+                    sync_tables(tables, items);
+                    continue
+                }
+            }
+        }
+
+        if let Some(old) = doc.get_mut(k).and_then(|i| i.as_value_mut()) {
+            let mut bare = old.clone();
+            bare.decor_mut().clear();
+            if parse_value(&bare.to_string()) != *v {
+                let decor = old.decor().clone();
+                *old = edit_value(v);
+                *old.decor_mut() = decor;
+            }
+            continue
+        }
+        doc.insert(k, toml_edit::Item::Value(edit_value(v)));
+    }
+}
+
+//sync each table of an array of tables, adding or dropping tables at the end
+fn sync_tables(doc: &mut toml_edit::ArrayOfTables, values: &[Value]) {
+    while doc.len() > values.len() {
+        let last = doc.len() - 1;
+        doc.remove(last);
+    }
+    for (i, v) in values.iter().enumerate() {
+        if let Value::Table(ref t) = *v {
+            if i == doc.len() {
+                doc.push(toml_edit::Table::new());
+            }
+            sync(doc.get_mut(i).unwrap(), t);
+        }
+    }
+}
+
+///! Toml backed config, made of layers merged in `Layer` order
+pub struct Config {
+    layers: BTreeMap<Layer, Table>,
+    //what each layer was parsed from, for line numbers in errors
+    sources: BTreeMap<Layer, String>,
+    //layers read from a file, for reloading
+    files: BTreeMap<Layer, String>,
+    watched: Option<BTreeMap<Layer, Option<SystemTime>>>,
+    handlers: Vec<(String, Box<ChangeHandler>)>,
+    //every layer merged
+    config: Value
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("layers", &self.layers)
+            .field("files", &self.files)
+            .field("handlers", &self.handlers.len())
+            .finish()
+    }
+}
+
+impl Config {
+    ///! Create a new empty config
+    pub fn new() -> Config {
+        Config {
+            layers: BTreeMap::new(),
+            sources: BTreeMap::new(),
+            files: BTreeMap::new(),
+            watched: None,
+            handlers: Vec::new(),
+            config: Value::Table(Table::new())
+        }
+    }
+
+    ///! Create config from a toml file, it's the user layer so `set` and `save` round trip it
+    pub fn from_file(config: &str) -> Result<Config, ConfigError> {
+        Config::new().with_file(Layer::User, config)
+    }
+
+    ///! Create config from toml text, as the user layer
+    pub fn parse(conf: &str) -> Result<Config, ConfigError> {
+        Config::new().with_toml(Layer::User, conf)
+    }
+
+    ///! The user layer from `config.toml` in app's config directory, created if it isn't there yet
+    pub fn load_user(app: &str) -> Result<Config, ConfigError> {
+        Config::load_user_from(&AppDirs::new(app)?)
+    }
+
+    ///! `load_user` with the config directory in dirs
+    pub fn load_user_from(dirs: &AppDirs) -> Result<Config, ConfigError> {
+        let path = dirs.config_file(USER_FILE)?;
+        Config::new().with_user_file(&path.to_string_lossy())
+    }
+
+    ///! Save the user layer to `config.toml` in app's config directory
+    pub fn save_user(&self, app: &str) -> Result<(), ConfigError> {
+        self.save_user_to(&AppDirs::new(app)?)
+    }
+
+    ///! `save_user` with the config directory in dirs
+    pub fn save_user_to(&self, dirs: &AppDirs) -> Result<(), ConfigError> {
+        let path = dirs.config_file(USER_FILE)?;
+        self.save(&path.to_string_lossy())
+    }
+
+    ///! Replace layer with toml text
+    pub fn with_toml(mut self, layer: Layer, conf: &str) -> Result<Self, ConfigError> {
+        self.layers.insert(layer, parse_table(conf)?);
+        self.sources.insert(layer, conf.to_string());
+        self.rebuild();
+        Ok(self)
+    }
+
+    ///! Replace the defaults layer with the defaults in schema
+    pub fn with_defaults(mut self, schema: &Schema) -> Self {
+        self.layers.insert(Layer::Defaults, schema.defaults());
+        //only for line numbers, the layer itself doesn't depend on it parsing
+        self.sources.insert(Layer::Defaults, schema.to_toml());
+        self.rebuild();
+        self
+    }
+
+    ///! Replace layer with a toml file, `reload` and `reload_modified` read it again
+    pub fn with_file(mut self, layer: Layer, config: &str) -> Result<Self, ConfigError> {
+        let conf = read_file(config)?;
+        self.files.insert(layer, config.to_string());
+        self.with_toml(layer, &conf)
+    }
+
+    ///! Like `with_file` for the user layer, but a missing file is just an empty layer
+    pub fn with_user_file(mut self, config: &str) -> Result<Self, ConfigError> {
+        self.files.insert(Layer::User, config.to_string());
+        match read_file(config) {
+            Ok(conf) => self.with_toml(Layer::User, &conf),
+            Err(ConfigError::IOError { ref source }) if source.kind() == std::io::ErrorKind::NotFound => Ok(self),
+            Err(e) => Err(e)
+        }
+    }
+
+    ///! Apply `PIPEWRENCH_` environment variables, `__` separates keys:
+    ///! `PIPEWRENCH_WINDOW__WIDTH=1920` sets `window.width`
+    pub fn with_env(self) -> Result<Self, ConfigError> {
+        //variables that aren't unicode can't be ours, skip them instead of panicking like `vars` does
+        let vars = std::env::vars_os().filter_map(|(var, value)| Some((var.into_string().ok()?, value.into_string().ok()?)));
+        self.with_vars(vars)
+    }
+
+    ///! `with_env` with the variables given instead of read from the environment
+    pub fn with_vars<I: IntoIterator<Item=(String, String)>>(mut self, vars: I) -> Result<Self, ConfigError> {
+        let mut table = Table::new();
+        for (var, value) in vars {
+            let name = match var.strip_prefix(ENV_PREFIX) {
+                Some(name) => name,
+                None => continue
+            };
+            let segments:Vec<&str> = name.split("__").collect();
+            insert(&mut table, &self.resolve(&segments), parse_value(&value))?;
+        }
+        self.layers.insert(Layer::Env, table);
+        self.sources.remove(&Layer::Env);
+        self.rebuild();
+        Ok(self)
+    }
+
+    ///! Apply every `--set key=value` (or `--set=key=value`) in args, anything else is ignored
+    pub fn with_args<I, S>(mut self, args: I) -> Result<Self, ConfigError> where I: IntoIterator<Item=S>, S: AsRef<str> {
+        let mut table = Table::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let arg = arg.as_ref();
+            let set = if arg == "--set" {
+                match args.next() {
+                    Some(next) => next.as_ref().to_string(),
+                    None => return Err(ConfigError::InvalidOverride(arg.to_string()))
+                }
+            }
+            else {
+                match arg.strip_prefix("--set=") {
+                    Some(set) => set.to_string(),
+                    None => continue
+                }
+            };
+
+            match set.find('=') {
+                Some(i) => insert(&mut table, set[..i].trim(), parse_value(set[i + 1..].trim()))?,
+                None => return Err(ConfigError::InvalidOverride(set))
+            }
+        }
+        self.layers.insert(Layer::Args, table);
+        self.sources.remove(&Layer::Args);
+        self.rebuild();
+        Ok(self)
+    }
+
+    //match env var segments to existing keys ignoring case, new keys are lowercase
+    fn resolve(&self, segments: &[&str]) -> String {
+        let mut node = Some(&self.config);
+        let mut keys = Vec::new();
+        for segment in segments {
+            let lower = segment.to_lowercase();
+            let key = node.and_then(|n| n.as_table())
+                .and_then(|t| t.keys().find(|k| k.to_lowercase() == lower).cloned())
+                .unwrap_or(lower);
+            node = node.and_then(|n| n.get(&key[..]));
+            keys.push(key);
+        }
+        keys.join(".")
+    }
+
+    //merge the layers again and tell handlers about every key that changed
+    fn rebuild(&mut self) -> Vec<String> {
+        let mut merged = Table::new();
+        for table in self.layers.values() {
+            merge(&mut merged, table);
+        }
+        let old = std::mem::replace(&mut self.config, Value::Table(merged));
+        let changed = match old {
+            Value::Table(ref old) => changed_keys(old, self.table()),
+            _ => unreachable!()
+        };
+
+        for key in &changed {
+            for (pattern, f) in &self.handlers {
+                if matches(pattern, key) {
+                    f(key, self.value(key));
+                }
+            }
+        }
+        changed
+    }
+
+    ///! Call `f` whenever a key matching pattern changes, by `set` or a reload.
+    ///! `keybind.*` matches every key in `[keybind]`, `window` everything under it and `*.width` any width one level down.
+    pub fn on_change(&mut self, pattern: &str, f: Box<ChangeHandler>) {
+        self.handlers.push((pattern.to_string(), f));
+    }
+
+    ///! Turn watching the config files on or off, `reload_modified` does the actual checking
+    pub fn watch(&mut self, enabled: bool) {
+        if !enabled {
+            self.watched = None;
+        }
+        else if self.watched.is_none() {
+            self.watched = Some(self.files.iter().map(|(&l, path)| (l, modified(path))).collect());
+        }
+    }
+
+    //read layer's file again, a missing user file empties the layer
+    fn read_layer(&mut self, layer: Layer) -> Result<(), ConfigError> {
+        let path = match self.files.get(&layer) {
+            Some(path) => path.clone(),
+            None => return Ok(())
+        };
+        let conf = match read_file(&path) {
+            Err(ConfigError::IOError { ref source }) if layer == Layer::User && source.kind() == std::io::ErrorKind::NotFound => String::new(),
+            other => other?
+        };
+        self.layers.insert(layer, parse_table(&conf)?);
+        self.sources.insert(layer, conf);
+        Ok(())
+    }
+
+    ///! Read every file layer again, returns the keys that changed
+    pub fn reload(&mut self) -> Result<Vec<String>, ConfigError> {
+        let layers:Vec<Layer> = self.files.keys().cloned().collect();
+        for layer in layers {
+            self.read_layer(layer)?;
+        }
+        Ok(self.rebuild())
+    }
+
+    ///! Reload the files that changed since they were read, returns the keys that changed.
+    ///! A file that fails to parse keeps its old values and is reported on stderr.
+    pub fn reload_modified(&mut self) -> Vec<String> {
+        let changed:Vec<(Layer, Option<SystemTime>)> = match self.watched {
+            Some(ref watched) => self.files.iter()
+                .map(|(&l, path)| (l, modified(path)))
+                .filter(|&(l, time)| watched.get(&l) != Some(&time))
+                .collect(),
+            None => return Vec::new()
+        };
+
+        for &(layer, time) in &changed {
+            if let Err(e) = self.read_layer(layer) {
+                println_err!("Failed to reload {}: {}", self.files[&layer], e);
+            }
+            //taken either way so a broken file isn't retried until it changes again
+            if let Some(ref mut watched) = self.watched {
+                watched.insert(layer, time);
+            }
+        }
+
+        if changed.is_empty() { Vec::new() } else { self.rebuild() }
+    }
+
+    ///! The highest layer that has name
+    pub fn source_of(&self, name: &str) -> Option<Layer> {
+        self.layers.iter().rev().find(|&(_, t)| find(t, name).is_some()).map(|(&l, _)| l)
+    }
+
+    ///! One layer on its own
+    pub fn layer(&self, layer: Layer) -> Option<&Table> {
+        self.layers.get(&layer)
+    }
+
+    ///! The user layer as toml text, keeping the comments and layout of whatever it was loaded from
+    pub fn to_user_toml(&self) -> String {
+        let mut doc = self.sources.get(&Layer::User)
+            .and_then(|text| text.parse::<toml_edit::DocumentMut>().ok())
+            .unwrap_or_default();
+        let empty = Table::new();
+        sync(doc.as_table_mut(), self.layers.get(&Layer::User).unwrap_or(&empty));
+        doc.to_string()
+    }
+
+    ///! Save the user layer to toml file, atomically through a temp file next to it
+    pub fn save(&self, config: &str) -> Result<(), ConfigError> {
+        let path = Path::new(config);
+        let tmp = path.with_file_name(format!(".{}.tmp", path.file_name().and_then(|n| n.to_str()).unwrap_or("config")));
+        {
+            let mut f = OpenOptions::new().write(true).truncate(true).create(true).open(&tmp)?;
+            f.write_all(self.to_user_toml().as_bytes())?;
+            f.sync_all()?;
+        }
+        if let Err(e) = fs::rename(&tmp, path) {
+            let _ = fs::remove_file(&tmp);
+            return Err(ConfigError::from(e))
+        }
+        Ok(())
+    }
+
+    ///! The whole config as a toml table
+    pub fn table(&self) -> &Table {
+        match self.config {
+            Value::Table(ref t) => t,
+            _ => unreachable!()
+        }
+    }
+
+    ///! Value at a dotted key path like `window.width`
+    pub fn value(&self, name: &str) -> Option<&Value> {
+        name.split('.').try_fold(&self.config, |v, k| v.get(k))
+    }
+
+    ///! Deserialize the value at name, whole sections work too:
+    ///! `config.get::<WindowConfig>("window")`
+    pub fn get<T: DeserializeOwned>(&self, name: &str) -> Result<T, ConfigError> {
+        let value = self.value(name).ok_or_else(|| ConfigError::MissingKey(name.to_string()))?;
+        serde_path_to_error::deserialize(value.clone()).map_err(|e| {
+            let key = match e.path().iter().count() {
+                0 => name.to_string(),
+                _ => format!("{}.{}", name, e.path())
+            };
+            ConfigError::InvalidValue {
+                line: self.line_of(&key),
+                key,
+                message: e.into_inner().to_string()
+            }
+        })
+    }
+
+    //line of key in the text its layer was parsed from, or of the nearest parent found
+    fn line_of(&self, key: &str) -> Option<usize> {
+        let text = self.sources.get(&self.source_of(key)?)?;
+        let doc = toml_edit::ImDocument::parse(&text[..]).ok()?;
+        let mut item = doc.as_item();
+        let mut span = None;
+        for k in key.split(['.', '[', ']']).filter(|k| !k.is_empty()) {
+            let next = match k.parse::<usize>() {
+                Ok(i) => item.get(i),
+                Err(_) => item.get(k)
+            };
+            item = match next {
+                Some(next) => next,
+                None => break
+            };
+            span = item.span().or(span);
+        }
+        span.map(|s| position(text, s.start).0)
+    }
+
+    pub fn value_int(&self, name: &str) -> Option<i64> {
+        let v = self.value(name);
+        if v.is_none() { return None }
+        v.unwrap().as_integer()
+    }
+
+    pub fn value_bool(&self, name: &str) -> Option<bool> {
+        let v = self.value(name);
+        if v.is_none() { return None }
+        v.unwrap().as_bool()
+    }
+
+    pub fn value_string<'a>(&'a self, name: &'a str) -> Option<&'a str> {
+        let v = self.value(name);
+        if v.is_none() { return None }
+        v.unwrap().as_str()
+    }
+
+    ///! Integers count as floats too
+    pub fn value_float(&self, name: &str) -> Option<f64> {
+        let v = self.value(name)?;
+        v.as_float().or_else(|| v.as_integer().map(|i| i as f64))
+    }
+
+    ///! The array at name as values, `get::<Vec<i64>>` gets it typed
+    pub fn value_array(&self, name: &str) -> Option<&Array> {
+        self.value(name)?.as_array()
+    }
+
+    pub fn value_table(&self, name: &str) -> Option<&Table> {
+        self.value(name)?.as_table()
+    }
+
+    pub fn value_datetime(&self, name: &str) -> Option<&Datetime> {
+        self.value(name)?.as_datetime()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.value(name).is_some()
+    }
+
+    ///! Keys of the table at name, the top level ones for an empty name
+    pub fn keys(&self, name: &str) -> Vec<&str> {
+        let table = if name.is_empty() { Some(self.table()) } else { self.value_table(name) };
+        table.map(|t| t.keys().map(|k| &k[..]).collect()).unwrap_or_default()
+    }
+
+    ///! Treat key as a Binding, in the format `Binding` reads and writes, state is used when it doesn't give one
+    pub fn keybinding(&self, name: &str, state: BindingState) -> Option<Binding> {
+        let text = match self.value(name) {
+            Some(Value::String(text)) => text,
+            Some(v) => {
+                println_err!("Keybind {} doesn't have a string value {}", name, v);
+                return None
+            },
+            None => {
+                println_err!("Keybind {} doesn't exist in config", name);
+                return None
+            }
+        };
+
+        match Binding::parse(text, state) {
+            Ok(binding) => Some(binding),
+            Err(e) => {
+                println_err!("Invalid keybind for {}: {}", name, e);
+                None
+            }
+        }
+    }
+
+    ///! `keybinding` for a key holding a binding or an array of them
+    pub fn keybindings(&self, name: &str, state: BindingState) -> Vec<Binding> {
+        let value = match self.value(name) {
+            Some(value) => value,
+            None => {
+                println_err!("Keybind {} doesn't exist in config", name);
+                return Vec::new()
+            }
+        };
+
+        match parse_bindings(value, state) {
+            Ok(bindings) => bindings,
+            Err(e) => {
+                println_err!("Invalid keybind for {}: {}", name, e);
+                Vec::new()
+            }
+        }
+    }
+
+    ///! Every binding in section, keys are commands parsed with `FromStr` and values are
+    ///! a binding or an array of them. A missing section has none.
+    pub fn bindings<T: FromStr + Clone>(&self, section: &str) -> Result<Vec<(T, Binding)>, ConfigError> {
+        let table = match self.value(section) {
+            Some(Value::Table(t)) => t,
+            Some(_) => return Err(ConfigError::InvalidValue {
+                key: section.to_string(),
+                line: self.line_of(section),
+                message: "expected a table of bindings".to_string()
+            }),
+            None => return Ok(Vec::new())
+        };
+
+        let mut bindings = Vec::new();
+        for (name, value) in table {
+            let key = format!("{}.{}", section, name);
+            let invalid = |message: String| ConfigError::InvalidValue { line: self.line_of(&key), key: key.clone(), message };
+            let command = T::from_str(name).map_err(|_| invalid(format!("unknown command {}", name)))?;
+            for binding in parse_bindings(value, BindingState::Pressed).map_err(invalid)? {
+                bindings.push((command.clone(), binding));
+            }
+        }
+        Ok(bindings)
+    }
+
+    ///! Set key to value in the user layer, if value already exists, it's overwritten
+    pub fn set<V: Into<Value>>(&mut self, name: &str, val: V) -> Result<(), ConfigError> {
+        self.set_in(Layer::User, name, val)
+    }
+
+    ///! `set` for any layer, only the user layer gets saved
+    pub fn set_in<V: Into<Value>>(&mut self, layer: Layer, name: &str, val: V) -> Result<(), ConfigError> {
+        insert(self.layers.entry(layer).or_default(), name, val.into())?;
+        self.rebuild();
+        Ok(())
+    }
+
+    ///! Serialize val into name in the user layer, the other way round from `get`
+    pub fn put<T: Serialize>(&mut self, name: &str, val: &T) -> Result<(), ConfigError> {
+        let value = Value::try_from(val).map_err(|e| ConfigError::InvalidValue {
+            key: name.to_string(),
+            line: None,
+            message: e.to_string()
+        })?;
+        self.set(name, value)
+    }
+
+    ///! Remove key from the user layer, returning what it had there.
+    ///! Other layers still apply, so the key may keep a value.
+    pub fn remove(&mut self, name: &str) -> Option<Value> {
+        self.remove_in(Layer::User, name)
+    }
+
+    ///! `remove` for any layer
+    pub fn remove_in(&mut self, layer: Layer, name: &str) -> Option<Value> {
+        let old = take(self.layers.get_mut(&layer)?, name)?;
+        self.rebuild();
+        Some(old)
+    }
+}
+
+#[test]
+fn insert_test() {
+    let mut c = Config::new();
+
+    match c.set("this.is.a.test", Value::Integer(12345)) {
+        Ok(_) => {},
+        Err(e) => panic!("{}", e)
+    }
+
+    assert_eq!(Some(12345), c.value_int("this.is.a.test"));
+    match c.set("another..test", Value::Boolean(false)) {
+        Ok(_) => panic!("That's not supposed to happen!"),
+        Err(ConfigError::EmptyKey) => {},
+        _ => unreachable!()
+    }
+
+    match c.set("this.is.a.test.too", Value::Integer(12345)) {
+        Ok(_) => {},
+        Err(e) => panic!("{}", e),
+    }
+
+    assert_eq!(Some(12345), c.value_int("this.is.a.test.too"));
+}
+
+#[test]
+fn typed_get() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Window {
+        width: u32,
+        height: u32,
+        fullscreen: bool
+    }
+
+    let c = Config::parse("[window]\nwidth = 1280\nheight = 720\nfullscreen = false\n\n[bad]\nwidth = 1\nheight = \"tall\"\nfullscreen = true\n").unwrap();
+    assert_eq!(c.get::<Window>("window").unwrap(), Window { width: 1280, height: 720, fullscreen: false });
+    assert_eq!(c.get::<u32>("window.width").unwrap(), 1280);
+    assert_eq!(c.value_int("window.height"), Some(720));
+
+    match c.get::<Window>("bad") {
+        Err(ConfigError::InvalidValue { ref key, line: Some(8), .. }) if key == "bad.height" => {},
+        other => panic!("{:?}", other)
+    }
+    match c.get::<Window>("nope") {
+        Err(ConfigError::MissingKey(ref k)) if k == "nope" => {},
+        other => panic!("{:?}", other)
+    }
+    match Config::parse("[window]\nwidth = = 3\n") {
+        Err(ConfigError::TomlParseError { line: 2, .. }) => {},
+        other => panic!("{:?}", other)
+    }
+}
+
+#[test]
+fn layers() {
+    let path = std::env::temp_dir().join("pipewrench_layers.toml").to_string_lossy().into_owned();
+    let _ = std::fs::remove_file(&path);
+
+    let mut c = Config::new()
+        .with_toml(Layer::Defaults, "[window]\nwidth = 640\nheight = 480\nvsync = true\n[keybind]\nForward = \"W\"").unwrap()
+        .with_toml(Layer::Game, "[window]\nwidth = 1280\nheight = 720").unwrap()
+        .with_user_file(&path).unwrap()
+        .with_vars(vec![("PIPEWRENCH_KEYBIND__FORWARD".to_string(), "Up".to_string()),
+                        ("PIPEWRENCH_WINDOW__VSYNC".to_string(), "false".to_string()),
+                        ("HOME".to_string(), "/home".to_string())]).unwrap()
+        .with_args(vec!["game", "--set", "window.width=1920", "--set=audio.volume=0.5"]).unwrap();
+
+    assert_eq!(c.value_int("window.width"), Some(1920));
+    assert_eq!(c.value_int("window.height"), Some(720));
+    assert_eq!(c.value_bool("window.vsync"), Some(false));
+    assert_eq!(c.value_string("keybind.Forward"), Some("Up"));
+    assert_eq!(c.get::<f64>("audio.volume").unwrap(), 0.5);
+
+    assert_eq!(c.source_of("window.width"), Some(Layer::Args));
+    assert_eq!(c.source_of("window.height"), Some(Layer::Game));
+    assert_eq!(c.source_of("keybind.Forward"), Some(Layer::Env));
+    assert_eq!(c.source_of("window"), Some(Layer::Args));
+    assert_eq!(c.source_of("nope"), None);
+
+    //the user layer sits under env and args, and is all that gets saved
+    c.set("window.height", config_int!(1080)).unwrap();
+    c.set("window.width", config_int!(800)).unwrap();
+    assert_eq!(c.value_int("window.height"), Some(1080));
+    assert_eq!(c.value_int("window.width"), Some(1920));
+    c.save(&path).unwrap();
+
+    let saved = Config::from_file(&path).unwrap();
+    assert_eq!(saved.value_int("window.height"), Some(1080));
+    assert_eq!(saved.value_bool("window.vsync"), None);
+    std::fs::remove_file(&path).unwrap();
+
+    match Config::new().with_args(vec!["--set", "window.width"]) {
+        Err(ConfigError::InvalidOverride(_)) => {},
+        other => panic!("{:?}", other)
+    }
+}
+
+#[test]
+fn preserving_save() {
+    let path = std::env::temp_dir().join("pipewrench_preserving_save.toml").to_string_lossy().into_owned();
+    let original = "# window settings\n[window]\nheight = 720 # pixels\nwidth  =  1280\n\n# keys\n[keybind]\nForward = \"W\"\nBack = \"S\"\n";
+    std::fs::write(&path, format!("{}{}", original, "#padding that must not survive\n".repeat(10))).unwrap();
+
+    let mut c = Config::parse(original).unwrap();
+    c.set("window.height", config_int!(1080)).unwrap();
+    c.set("audio.volume", config_int!(3)).unwrap();
+    c.save(&path).unwrap();
+
+    let saved = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(saved, "# window settings\n[window]\nheight = 1080 # pixels\nwidth  =  1280\n\n# keys\n[keybind]\nForward = \"W\"\nBack = \"S\"\n\n[audio]\nvolume = 3\n");
+    std::fs::remove_file(&path).unwrap();
+
+    //nothing loaded, the file gets created
+    let mut fresh = Config::new();
+    fresh.set("window.width", config_int!(640)).unwrap();
+    fresh.save(&path).unwrap();
+    assert_eq!(Config::from_file(&path).unwrap().value_int("window.width"), Some(640));
+    std::fs::remove_file(&path).unwrap();
+
+    //arrays of tables keep their headers and comments
+    let original = "# spawn points
+[[spawn]]
+x = 1 # left
+y = 2
+
+[[spawn]]
+x = 5
+y = 6
+";
+    let mut c = Config::parse(original).unwrap();
+    assert_eq!(c.to_user_toml(), original);
+    let mut spawns = c.value_array("spawn").unwrap().clone();
+    spawns[1] = config_table!{"x" => 7, "y" => 6};
+    spawns.push(config_table!{"x" => 9, "y" => 9});
+    c.set("spawn", Value::Array(spawns)).unwrap();
+    assert_eq!(c.to_user_toml(), "# spawn points
+[[spawn]]
+x = 1 # left
+y = 2
+
+[[spawn]]
+x = 7
+y = 6
+
+[[spawn]]
+x = 9
+y = 9
+");
+
+    let first = c.value_array("spawn").unwrap()[..1].to_vec();
+    c.set("spawn", Value::Array(first)).unwrap();
+    assert_eq!(c.to_user_toml(), "# spawn points
+[[spawn]]
+x = 1 # left
+y = 2
+");
+    assert_eq!(Config::parse(&c.to_user_toml()).unwrap().table(), c.table());
+}
+
+#[test]
+fn live_reload() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    let path = std::env::temp_dir().join("pipewrench_live_reload.toml").to_string_lossy().into_owned();
+    let write = |text: &str, age: u64| {
+        let f = File::create(&path).unwrap();
+        (&f).write_all(text.as_bytes()).unwrap();
+        f.set_modified(SystemTime::now() - Duration::from_secs(age)).unwrap();
+    };
+    write("[window]\nwidth = 1280\n[keybind]\nForward = \"W\"\nBack = \"S\"\n", 60);
+
+    let mut c = Config::new()
+        .with_toml(Layer::Defaults, "[window]\nheight = 720").unwrap()
+        .with_file(Layer::Game, &path).unwrap();
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let keys = seen.clone();
+    c.on_change("keybind.*", Box::new(move |k, v| keys.borrow_mut().push((k.to_string(), v.cloned()))));
+    let windows = Rc::new(RefCell::new(0));
+    let counter = windows.clone();
+    c.on_change("window", Box::new(move |_, _| *counter.borrow_mut() += 1));
+    c.watch(true);
+    assert!(c.reload_modified().is_empty());
+
+    write("[window]\nwidth = 1920\n[keybind]\nForward = \"Up\"\nFire = \"Space\"\n", 0);
+    assert_eq!(c.reload_modified(), vec!["keybind.Back", "keybind.Fire", "keybind.Forward", "window.width"]);
+    assert_eq!(c.value_int("window.width"), Some(1920));
+    assert_eq!(*seen.borrow(), vec![
+        ("keybind.Back".to_string(), None),
+        ("keybind.Fire".to_string(), Some(Value::String("Space".to_string()))),
+        ("keybind.Forward".to_string(), Some(Value::String("Up".to_string())))]);
+    assert_eq!(*windows.borrow(), 1);
+
+    //broken files keep the old values
+    write("[window\n", 30);
+    assert!(c.reload_modified().is_empty());
+    assert_eq!(c.value_string("keybind.Forward"), Some("Up"));
+
+    c.set("window.height", config_int!(1080)).unwrap();
+    c.set("window.height", config_int!(1080)).unwrap();
+    assert_eq!(*windows.borrow(), 2);
+
+    assert!(matches("*.width", "window.width"));
+    assert!(!matches("*.width", "window.height"));
+    assert!(!matches("keybind.*", "keybind"));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn value_coverage() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Audio {
+        volume: f64,
+        muted: bool
+    }
+
+    let mut c = Config::new()
+        .with_toml(Layer::Game, "[window]\nwidth = 1280\nscale = 1.5\nsizes = [640, 1280]\n[save]\nlast = 2024-01-02T03:04:05Z").unwrap();
+    c.set("window.height", 720).unwrap();
+    c.set("window.title", "pipewrench").unwrap();
+    c.set("window.modes", config_array!["windowed", "fullscreen"]).unwrap();
+    c.set("window.position", config_table!{"x" => 10, "y" => config_float!(2.5)}).unwrap();
+    c.put("audio", &Audio { volume: 0.5, muted: false }).unwrap();
+
+    assert_eq!(c.value_float("window.scale"), Some(1.5));
+    assert_eq!(c.value_float("window.width"), Some(1280.0));
+    assert_eq!(c.value_array("window.sizes").map(|a| a.len()), Some(2));
+    assert_eq!(c.get::<Vec<i64>>("window.sizes").unwrap(), vec![640, 1280]);
+    assert_eq!(c.get::<Vec<String>>("window.modes").unwrap(), vec!["windowed", "fullscreen"]);
+    assert_eq!(c.value_float("window.position.y"), Some(2.5));
+    assert_eq!(c.value_table("window.position").map(|t| t.len()), Some(2));
+    assert_eq!(c.value_datetime("save.last").map(|d| d.to_string()), Some("2024-01-02T03:04:05Z".to_string()));
+    assert_eq!(c.get::<Audio>("audio").unwrap(), Audio { volume: 0.5, muted: false });
+
+    assert_eq!(c.keys(""), vec!["audio", "save", "window"]);
+    assert_eq!(c.keys("window"), vec!["height", "modes", "position", "scale", "sizes", "title", "width"]);
+    assert!(c.keys("window.width").is_empty());
+
+    //removing only takes the user layer's value away
+    assert!(c.contains("window.title"));
+    assert_eq!(c.remove("window.title"), Some(config_string!("pipewrench")));
+    assert!(!c.contains("window.title"));
+    assert_eq!(c.remove("window.width"), None);
+    assert!(c.contains("window.width"));
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+#[test]
+fn user_dirs() {
+    let root = std::env::temp_dir().join("pipewrench_user_dirs");
+    let _ = std::fs::remove_dir_all(&root);
+    let config_home = root.to_string_lossy().into_owned();
+    let dirs = AppDirs::from_vars("game", |var| match var {
+        "XDG_CONFIG_HOME" | "HOME" => Some(config_home.clone()),
+        _ => None
+    }).unwrap();
+
+    //first run, nothing there yet
+    let mut c = Config::load_user_from(&dirs).unwrap().with_toml(Layer::Defaults, "[window]\nwidth = 640").unwrap();
+    assert_eq!(c.value_int("window.width"), Some(640));
+    c.set("window.width", 1920).unwrap();
+    c.save_user_to(&dirs).unwrap();
+    assert!(root.join("game/config.toml").is_file());
+
+    assert_eq!(Config::load_user_from(&dirs).unwrap().value_int("window.width"), Some(1920));
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn binding_round_trip() {
+    use input::{Axis, AxisDirection, Input, Keycode};
+
+    let mut input = Input::<String>::new(None)
+        .add_binding(Some(Binding::Key(BindingState::Held, Keycode::W)), "Forward".to_string())
+        .add_binding(Some(Binding::Key(BindingState::Held, Keycode::Up)), "Forward".to_string())
+        .add_binding(Some("Ctrl+S".parse().unwrap()), "Save".to_string())
+        .add_binding(Some("Released Mouse Right".parse().unwrap()), "Zoom".to_string());
+
+    let mut c = Config::parse("[keybind]\nFire = \"Space\"\n").unwrap();
+    input.save_bindings(&mut c, "keybind").unwrap();
+    assert_eq!(c.value_string("keybind.Save"), Some("Pressed Ctrl+S"));
+    assert_eq!(c.get::<Vec<String>>("keybind.Forward").unwrap(), vec!["Held Up", "Held W"]);
+    assert_eq!(c.get::<Vec<String>>("keybind.Fire").unwrap(), Vec::<String>::new());
+
+    //a rebinding menu changes one and loads them back
+    c.set("keybind.Zoom", "Held Pad lefttrigger+").unwrap();
+    input.load_bindings(&c, "keybind").unwrap();
+    assert_eq!(input.bindings().len(), 4);
+    assert_eq!(input.bindings().get(&Binding::Axis(BindingState::Held, Axis::TriggerLeft, AxisDirection::Positive)), Some(&"Zoom".to_string()));
+    assert_eq!(c.keybinding("keybind.Save", BindingState::Held), Some("Ctrl+S".parse().unwrap()));
+    assert_eq!(Config::parse("[k]\nJump = \"Space\"").unwrap().keybinding("k.Jump", BindingState::Held),
+               Some(Binding::Key(BindingState::Held, Keycode::Space)));
+
+    match Config::parse("[keybind]\nJump = \"Space\"\nFire = \"Nope\"").unwrap().bindings::<String>("keybind") {
+        Err(ConfigError::InvalidValue { ref key, line: Some(3), .. }) if key == "keybind.Fire" => {},
+        other => panic!("{:?}", other)
+    }
+    match Config::parse("[keybind]\nJump = 3").unwrap().bindings::<String>("keybind") {
+        Err(ConfigError::InvalidValue { .. }) => {},
+        other => panic!("{:?}", other)
+    }
+    assert!(c.bindings::<String>("nope").unwrap().is_empty());
+
+    //unbinding something the game layer binds sticks through a save and load
+    let mut c = Config::new()
+        .with_toml(Layer::Game, "[keybind]\nFire = \"Space\"\nJump = \"Up\"\n").unwrap()
+        .with_toml(Layer::User, "[keybind]\nJump = \"W\"\n").unwrap();
+    let mut input = Input::<String>::new(None);
+    input.load_bindings(&c, "keybind").unwrap();
+    assert_eq!(input.bindings().len(), 2);
+    input.unbind(&Binding::Key(BindingState::Pressed, Keycode::Space));
+    input.save_bindings(&mut c, "keybind").unwrap();
+    assert_eq!(c.to_user_toml(), "[keybind]\nJump = \"Pressed W\"\nFire = []\n");
+
+    input.load_bindings(&c, "keybind").unwrap();
+    assert_eq!(input.bindings().len(), 1);
+    assert_eq!(input.bindings().get(&Binding::Key(BindingState::Pressed, Keycode::W)), Some(&"Jump".to_string()));
+}
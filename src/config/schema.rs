@@ -0,0 +1,311 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use super::{leaves, matches, Config, ConfigError, Table, Value};
+
+///! The type a config value must have
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    Boolean,
+    Integer,
+    ///! Integers are accepted too
+    Float,
+    String,
+    Array,
+    Datetime
+}
+
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ValueKind::Boolean => "boolean",
+            ValueKind::Integer => "integer",
+            ValueKind::Float => "float",
+            ValueKind::String => "string",
+            ValueKind::Array => "array",
+            ValueKind::Datetime => "datetime"
+        })
+    }
+}
+
+impl ValueKind {
+    fn accepts(&self, value: &Value) -> bool {
+        matches!((*self, value),
+            (ValueKind::Boolean, &Value::Boolean(_)) |
+            (ValueKind::Integer, &Value::Integer(_)) |
+            (ValueKind::Float, &Value::Float(_)) | (ValueKind::Float, &Value::Integer(_)) |
+            (ValueKind::String, &Value::String(_)) |
+            (ValueKind::Array, &Value::Array(_)) |
+            (ValueKind::Datetime, &Value::Datetime(_)))
+    }
+}
+
+///! What one key may hold, keys without a default are required
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeySchema {
+    kind: ValueKind,
+    range: (Option<f64>, Option<f64>),
+    allowed: Vec<Value>,
+    default: Option<Value>,
+    description: String
+}
+
+impl KeySchema {
+    pub fn new(kind: ValueKind) -> KeySchema {
+        KeySchema {
+            kind,
+            range: (None, None),
+            allowed: Vec::new(),
+            default: None,
+            description: String::new()
+        }
+    }
+
+    pub fn boolean() -> KeySchema { KeySchema::new(ValueKind::Boolean) }
+    pub fn integer() -> KeySchema { KeySchema::new(ValueKind::Integer) }
+    pub fn float() -> KeySchema { KeySchema::new(ValueKind::Float) }
+    pub fn string() -> KeySchema { KeySchema::new(ValueKind::String) }
+    pub fn array() -> KeySchema { KeySchema::new(ValueKind::Array) }
+
+    ///! Inclusive bounds for integers and floats
+    pub fn with_range(mut self, min: f64, max: f64) -> Self {
+        self.range = (Some(min), Some(max));
+        self
+    }
+
+    pub fn with_min(mut self, min: f64) -> Self {
+        self.range.0 = Some(min);
+        self
+    }
+
+    pub fn with_max(mut self, max: f64) -> Self {
+        self.range.1 = Some(max);
+        self
+    }
+
+    ///! The only values the key may have: `with_allowed(vec!["windowed", "fullscreen"])`
+    pub fn with_allowed<I, V>(mut self, values: I) -> Self where I: IntoIterator<Item=V>, V: Into<Value> {
+        self.allowed = values.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_default<V: Into<Value>>(mut self, value: V) -> Self {
+        self.default = Some(value.into());
+        self
+    }
+
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = description.to_string();
+        self
+    }
+
+    pub fn kind(&self) -> ValueKind {
+        self.kind
+    }
+
+    pub fn default_value(&self) -> Option<&Value> {
+        self.default.as_ref()
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    ///! The values allowed, empty when anything of the right kind goes
+    pub fn allowed(&self) -> &[Value] {
+        &self.allowed
+    }
+
+    ///! What's wrong with value, if anything
+    pub fn check(&self, value: &Value) -> Result<(), String> {
+        if !self.kind.accepts(value) {
+            return Err(format!("expected {}, found {}", self.kind, value.type_str()))
+        }
+
+        let number = value.as_float().or_else(|| value.as_integer().map(|i| i as f64));
+        if let Some(n) = number {
+            let (min, max) = self.range;
+            if min.map(|m| n < m).unwrap_or(false) || max.map(|m| n > m).unwrap_or(false) {
+                return Err(format!("{} isn't in {}", value, self.range_text()))
+            }
+        }
+
+        if !self.allowed.is_empty() && !self.allowed.contains(value) {
+            return Err(format!("{} isn't one of {}", value, self.allowed_text()))
+        }
+        Ok(())
+    }
+
+    fn range_text(&self) -> String {
+        match self.range {
+            (Some(min), Some(max)) => format!("{} to {}", min, max),
+            (Some(min), None) => format!("at least {}", min),
+            (None, Some(max)) => format!("at most {}", max),
+            (None, None) => String::new()
+        }
+    }
+
+    fn allowed_text(&self) -> String {
+        self.allowed.iter().map(|v| v.to_string()).collect::<Vec<String>>().join(", ")
+    }
+}
+
+///! Every key a config may have, by dotted path. `keybind.*` style keys cover any key in a table.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schema {
+    keys: BTreeMap<String, KeySchema>
+}
+
+//edit distance, for suggesting what a misspelled key meant
+fn distance(a: &str, b: &str) -> usize {
+    let b:Vec<char> = b.chars().collect();
+    let mut row:Vec<usize> = (0..b.len() + 1).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for j in 0..b.len() {
+            let cost = if ca == b[j] { prev } else { prev + 1 };
+            prev = row[j + 1];
+            row[j + 1] = cost.min(row[j] + 1).min(prev + 1);
+        }
+    }
+    row[b.len()]
+}
+
+//a bare or quoted toml key for each segment of name
+fn toml_key(name: &str) -> String {
+    name.split('.').map(|k| toml_edit::Key::new(k).display_repr().into_owned()).collect::<Vec<String>>().join(".")
+}
+
+impl Schema {
+    pub fn new() -> Schema {
+        Schema::default()
+    }
+
+    pub fn with_key(mut self, name: &str, key: KeySchema) -> Self {
+        self.keys.insert(name.to_string(), key);
+        self
+    }
+
+    pub fn key(&self, name: &str) -> Option<&KeySchema> {
+        self.keys.get(name).or_else(|| self.keys.iter().find(|&(k, _)| k.contains('*') && matches(k, name)).map(|(_, s)| s))
+    }
+
+    pub fn keys(&self) -> Vec<&str> {
+        self.keys.keys().map(|k| &k[..]).collect()
+    }
+
+    ///! Every default as a table, for the defaults layer
+    pub fn defaults(&self) -> Table {
+        let mut table = Table::new();
+        for (name, key) in &self.keys {
+            if let Some(ref v) = key.default {
+                super::insert(&mut table, name, v.clone()).expect("schema keys aren't empty");
+            }
+        }
+        table
+    }
+
+    ///! Check every value in config, errors name the key and the line it's on.
+    ///! Unknown keys suggest the closest known one.
+    pub fn validate(&self, config: &Config) -> Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
+        let mut values = BTreeMap::new();
+        leaves("", config.table(), &mut values);
+
+        for (name, value) in &values {
+            let message = match self.key(name) {
+                Some(key) => key.check(value).err(),
+                None => {
+                    let suggestion = self.keys.keys()
+                        .filter(|k| !k.contains('*'))
+                        .map(|k| (distance(k, name), k))
+                        .filter(|&(d, _)| d <= 2)
+                        .min()
+                        .map(|(_, k)| k.clone());
+                    errors.push(ConfigError::UnknownKey { key: name.clone(), suggestion });
+                    continue
+                }
+            };
+            if let Some(message) = message {
+                errors.push(ConfigError::InvalidValue { key: name.clone(), line: config.line_of(name), message });
+            }
+        }
+
+        for (name, key) in &self.keys {
+            if key.default.is_none() && !name.contains('*') && !values.contains_key(name) {
+                errors.push(ConfigError::MissingKey(name.clone()));
+            }
+        }
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    ///! A default config file, each key commented with its description, type and limits.
+    ///! Keys without a default are left commented out.
+    pub fn to_toml(&self) -> String {
+        let mut sections:BTreeMap<&str, Vec<(&str, &KeySchema)>> = BTreeMap::new();
+        for (name, key) in &self.keys {
+            if name.contains('*') { continue }
+            let (table, key_name) = match name.rfind('.') {
+                Some(i) => (&name[..i], &name[i + 1..]),
+                None => ("", &name[..])
+            };
+            sections.entry(table).or_default().push((key_name, key));
+        }
+
+        let mut out = String::new();
+        for (table, keys) in sections {
+            if !table.is_empty() {
+                if !out.is_empty() { out.push('\n'); }
+                out.push_str(&format!("[{}]\n", toml_key(table)));
+            }
+            for (name, key) in keys {
+                for line in key.description.lines() {
+                    out.push_str(format!("# {}", line).trim_end());
+                    out.push('\n');
+                }
+                let mut limits = vec![key.kind.to_string()];
+                if key.range != (None, None) { limits.push(key.range_text()); }
+                if !key.allowed.is_empty() { limits.push(format!("one of {}", key.allowed_text())); }
+                out.push_str(&format!("# {}\n", limits.join(", ")));
+                match key.default {
+                    Some(ref v) => out.push_str(&format!("{} = {}\n", toml_key(name), v)),
+                    None => out.push_str(&format!("# {} =\n", toml_key(name)))
+                }
+            }
+        }
+        out
+    }
+}
+
+#[test]
+fn schema_validation() {
+    let schema = Schema::new()
+        .with_key("window.width", KeySchema::integer().with_range(320.0, 7680.0).with_default(1280).with_description("Width in pixels"))
+        .with_key("window.height", KeySchema::integer().with_range(240.0, 4320.0).with_default(720))
+        .with_key("window.mode", KeySchema::string().with_allowed(vec!["windowed", "fullscreen"]).with_default("windowed").with_description("How the window is shown\n\nborderless isn't supported yet"))
+        .with_key("audio.volume", KeySchema::float().with_range(0.0, 1.0).with_default(0.8))
+        .with_key("player.name", KeySchema::string())
+        .with_key("keybind.*", KeySchema::string());
+
+    let c = Config::new()
+        .with_defaults(&schema)
+        .with_toml(super::Layer::User, "[window]\nwidht = 1280\nheight = 100\nmode = \"borderless\"\n[audio]\nvolume = 1\n[keybind]\nFire = \"E\"\nJump = 3\n").unwrap();
+    assert_eq!(c.value_int("window.width"), Some(1280));
+
+    let errors:Vec<String> = schema.validate(&c).unwrap_err().iter().map(|e| e.to_string()).collect();
+    assert_eq!(errors, vec![
+        "Invalid value for \"keybind.Jump\" at line 9: expected string, found integer",
+        "Invalid value for \"window.height\" at line 3: 100 isn't in 240 to 4320",
+        "Invalid value for \"window.mode\" at line 4: \"borderless\" isn't one of \"windowed\", \"fullscreen\"",
+        "Unknown key \"window.widht\", did you mean \"window.width\"?",
+        "Config has no key \"player.name\""]);
+
+    let generated = schema.to_toml();
+    assert_eq!(generated, "[audio]\n# float, 0 to 1\nvolume = 0.8\n\n[player]\n# string\n# name =\n\n[window]\n# integer, 240 to 4320\nheight = 720\n# How the window is shown\n#\n# borderless isn't supported yet\n# string, one of \"windowed\", \"fullscreen\"\nmode = \"windowed\"\n# Width in pixels\n# integer, 320 to 7680\nwidth = 1280\n");
+    let defaults = Config::parse(&generated).unwrap();
+    assert_eq!(schema.validate(&defaults).unwrap_err().len(), 1);
+    //the only difference is the empty [player] holding the commented out name
+    assert_eq!(defaults.table()["window"], schema.defaults()["window"]);
+    assert_eq!(defaults.table()["audio"], schema.defaults()["audio"]);
+}
@@ -0,0 +1,127 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+///! Where a game keeps its settings, save data and cache.
+///! XDG base directories on Linux, `%APPDATA%` on Windows and `~/Library` on macOS.
+///! Directories are created the first time they're asked for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppDirs {
+    config: PathBuf,
+    data: PathBuf,
+    cache: PathBuf
+}
+
+//absolute paths only, the XDG spec says to ignore relative ones
+fn absolute(value: Option<String>) -> Option<PathBuf> {
+    value.map(PathBuf::from).filter(|p| p.is_absolute())
+}
+
+fn no_home() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "no home directory to put app directories in")
+}
+
+fn created(dir: &Path) -> io::Result<&Path> {
+    fs::create_dir_all(dir)?;
+    Ok(dir)
+}
+
+impl AppDirs {
+    ///! Directories for app, from the environment
+    pub fn new(app: &str) -> io::Result<AppDirs> {
+        AppDirs::from_vars(app, |var| std::env::var(var).ok())
+    }
+
+    ///! `new` with environment variables looked up through var
+    #[cfg(not(any(windows, target_os = "macos")))]
+    pub fn from_vars<F: Fn(&str) -> Option<String>>(app: &str, var: F) -> io::Result<AppDirs> {
+        let home = absolute(var("HOME"));
+        let base = |xdg: &str, fallback: &str| absolute(var(xdg))
+            .or_else(|| home.as_ref().map(|h| h.join(fallback)))
+            .map(|dir| dir.join(app))
+            .ok_or_else(no_home);
+
+        Ok(AppDirs {
+            config: base("XDG_CONFIG_HOME", ".config")?,
+            data: base("XDG_DATA_HOME", ".local/share")?,
+            cache: base("XDG_CACHE_HOME", ".cache")?
+        })
+    }
+
+    ///! `new` with environment variables looked up through var
+    #[cfg(windows)]
+    pub fn from_vars<F: Fn(&str) -> Option<String>>(app: &str, var: F) -> io::Result<AppDirs> {
+        let roaming = absolute(var("APPDATA")).ok_or_else(no_home)?.join(app);
+        let local = absolute(var("LOCALAPPDATA")).map(|l| l.join(app)).unwrap_or_else(|| roaming.clone());
+        Ok(AppDirs {
+            config: roaming.join("config"),
+            data: roaming.join("data"),
+            cache: local.join("cache")
+        })
+    }
+
+    ///! `new` with environment variables looked up through var
+    #[cfg(target_os = "macos")]
+    pub fn from_vars<F: Fn(&str) -> Option<String>>(app: &str, var: F) -> io::Result<AppDirs> {
+        let library = absolute(var("HOME")).ok_or_else(no_home)?.join("Library");
+        Ok(AppDirs {
+            config: library.join("Preferences").join(app),
+            data: library.join("Application Support").join(app),
+            cache: library.join("Caches").join(app)
+        })
+    }
+
+    ///! Settings, like `~/.config/app`
+    pub fn config_dir(&self) -> io::Result<&Path> {
+        created(&self.config)
+    }
+
+    ///! Save games and anything else worth keeping, like `~/.local/share/app`
+    pub fn data_dir(&self) -> io::Result<&Path> {
+        created(&self.data)
+    }
+
+    ///! Things that can be rebuilt, like `~/.cache/app`
+    pub fn cache_dir(&self) -> io::Result<&Path> {
+        created(&self.cache)
+    }
+
+    pub fn config_file(&self, name: &str) -> io::Result<PathBuf> {
+        Ok(self.config_dir()?.join(name))
+    }
+
+    pub fn data_file(&self, name: &str) -> io::Result<PathBuf> {
+        Ok(self.data_dir()?.join(name))
+    }
+
+    pub fn cache_file(&self, name: &str) -> io::Result<PathBuf> {
+        Ok(self.cache_dir()?.join(name))
+    }
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+#[test]
+fn app_dirs() {
+    let root = std::env::temp_dir().join("pipewrench_app_dirs");
+    let _ = fs::remove_dir_all(&root);
+    let home = root.join("home").to_string_lossy().into_owned();
+    let config = root.join("xdg_config").to_string_lossy().into_owned();
+
+    let dirs = AppDirs::from_vars("game", |var| match var {
+        "HOME" => Some(home.clone()),
+        "XDG_CONFIG_HOME" => Some(config.clone()),
+        "XDG_DATA_HOME" => Some("relative/data".to_string()),
+        _ => None
+    }).unwrap();
+
+    //nothing exists until it's asked for
+    assert!(!root.exists());
+    assert_eq!(dirs.config_file("config.toml").unwrap(), root.join("xdg_config/game/config.toml"));
+    assert_eq!(dirs.data_dir().unwrap(), root.join("home/.local/share/game"));
+    assert_eq!(dirs.cache_dir().unwrap(), root.join("home/.cache/game"));
+    assert!(root.join("xdg_config/game").is_dir());
+    assert!(root.join("home/.local/share/game").is_dir());
+
+    assert_eq!(AppDirs::from_vars("game", |_| None).unwrap_err().kind(), io::ErrorKind::NotFound);
+    fs::remove_dir_all(&root).unwrap();
+}
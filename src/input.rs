@@ -1,26 +1,203 @@
 use sdl2::keyboard::KeyboardState;
+use sdl2::mouse::MouseState;
+use sdl2::controller::GameController;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use std::str::FromStr;
+#[cfg(feature = "config")]
+use config::{parse_bindings, Config, ConfigError, Table, Value};
 
 pub use sdl2::keyboard::Keycode;
+pub use sdl2::mouse::MouseButton;
+pub use sdl2::controller::{Axis, Button};
 #[derive(Debug,PartialEq,Hash,Clone)]
 pub enum BindingState {
     Pressed,
-    Held
+    Held,
+    Released
 }
 
+///! Modifier keys held along with a key, left or right both count
+#[derive(Debug,Clone,Copy,Default,PartialEq,Eq,Hash)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool
+}
+
+impl Modifiers {
+    pub fn is_empty(&self) -> bool {
+        !(self.ctrl || self.shift || self.alt)
+    }
+
+    fn held(keys: &HashSet<Keycode>) -> Modifiers {
+        Modifiers {
+            ctrl: keys.contains(&Keycode::LCtrl) || keys.contains(&Keycode::RCtrl),
+            shift: keys.contains(&Keycode::LShift) || keys.contains(&Keycode::RShift),
+            alt: keys.contains(&Keycode::LAlt) || keys.contains(&Keycode::RAlt)
+        }
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash)]
+pub enum AxisDirection {
+    Positive,
+    Negative
+}
+
+///! Bindings read and write as text like `Held W`, `Pressed Ctrl+S`, `Released Mouse Left`,
+///! `Pressed Pad a` and `Held Pad leftx-`. The state can be left off when parsing.
 #[derive(Debug,PartialEq,Hash,Clone)]
 pub enum Binding {
     Key(BindingState, Keycode),
+    ///! A key with exactly these modifiers held
+    Combo(BindingState, Modifiers, Keycode),
+    Mouse(BindingState, MouseButton),
+    Button(BindingState, Button),
+    ///! A stick or trigger pushed more than halfway one way
+    Axis(BindingState, Axis, AxisDirection),
 }
 
 impl Eq for Binding {}
 
+#[derive(Debug, Error)]
+pub enum BindingError {
+    #[error("Binding is empty")]
+    Empty,
+    #[error("Unknown key \"{0}\"")]
+    UnknownKey(String),
+    #[error("Unknown mouse button \"{0}\"")]
+    UnknownMouseButton(String),
+    #[error("Unknown gamepad control \"{0}\"")]
+    UnknownPadControl(String)
+}
+
+const MOUSE_BUTTONS: [(MouseButton, &str); 5] = [(MouseButton::Left, "Left"), (MouseButton::Middle, "Middle"),
+    (MouseButton::Right, "Right"), (MouseButton::X1, "X1"), (MouseButton::X2, "X2")];
+const PAD_BUTTONS: [Button; 15] = [Button::A, Button::B, Button::X, Button::Y, Button::Back, Button::Guide, Button::Start,
+    Button::LeftStick, Button::RightStick, Button::LeftShoulder, Button::RightShoulder,
+    Button::DPadUp, Button::DPadDown, Button::DPadLeft, Button::DPadRight];
+const PAD_AXES: [Axis; 6] = [Axis::LeftX, Axis::LeftY, Axis::RightX, Axis::RightY, Axis::TriggerLeft, Axis::TriggerRight];
+const AXIS_THRESHOLD: i16 = 16384;
+
+//strip prefix from text ignoring case
+fn strip_word<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    match text.get(..prefix.len()) {
+        Some(start) if start.eq_ignore_ascii_case(prefix) => Some(&text[prefix.len()..]),
+        _ => None
+    }
+}
+
+impl Binding {
+    pub fn state(&self) -> &BindingState {
+        match *self {
+            Binding::Key(ref s, _) | Binding::Combo(ref s, _, _) | Binding::Mouse(ref s, _) |
+            Binding::Button(ref s, _) | Binding::Axis(ref s, _, _) => s
+        }
+    }
+
+    ///! Read the text format, with state used when the text doesn't give one
+    pub fn parse(text: &str, state: BindingState) -> Result<Binding, BindingError> {
+        let mut text = text.trim();
+        let mut state = state;
+        for &(word, ref s) in &[("Pressed ", BindingState::Pressed), ("Held ", BindingState::Held), ("Released ", BindingState::Released)] {
+            if let Some(rest) = strip_word(text, word) {
+                text = rest.trim_start();
+                state = s.clone();
+            }
+        }
+        if text.is_empty() {
+            return Err(BindingError::Empty)
+        }
+
+        if let Some(name) = strip_word(text, "Mouse ") {
+            let name = name.trim();
+            return MOUSE_BUTTONS.iter().find(|b| b.1.eq_ignore_ascii_case(name))
+                .map(|b| Binding::Mouse(state, b.0))
+                .ok_or_else(|| BindingError::UnknownMouseButton(name.to_string()))
+        }
+
+        if let Some(name) = strip_word(text, "Pad ") {
+            let name = name.trim().to_lowercase();
+            if let Some(button) = Button::from_string(&name) {
+                return Ok(Binding::Button(state, button))
+            }
+            let (axis, direction) = match name.char_indices().last() {
+                Some((i, '+')) => (&name[..i], AxisDirection::Positive),
+                Some((i, '-')) => (&name[..i], AxisDirection::Negative),
+                _ => return Err(BindingError::UnknownPadControl(name.clone()))
+            };
+            return Axis::from_string(axis)
+                .map(|a| Binding::Axis(state, a, direction))
+                .ok_or_else(|| BindingError::UnknownPadControl(name.clone()))
+        }
+
+        let mut mods = Modifiers::default();
+        loop {
+            if let Some(rest) = strip_word(text, "Ctrl+") { mods.ctrl = true; text = rest; }
+            else if let Some(rest) = strip_word(text, "Shift+") { mods.shift = true; text = rest; }
+            else if let Some(rest) = strip_word(text, "Alt+") { mods.alt = true; text = rest; }
+            else { break }
+        }
+        let keycode = Keycode::from_name(text).ok_or_else(|| BindingError::UnknownKey(text.to_string()))?;
+        Ok(if mods.is_empty() { Binding::Key(state, keycode) } else { Binding::Combo(state, mods, keycode) })
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} ", self.state())?;
+        match *self {
+            Binding::Key(_, kc) => write!(f, "{}", kc.name()),
+            Binding::Combo(_, mods, kc) => {
+                for &(held, name) in &[(mods.ctrl, "Ctrl+"), (mods.shift, "Shift+"), (mods.alt, "Alt+")] {
+                    if held { f.write_str(name)?; }
+                }
+                write!(f, "{}", kc.name())
+            },
+            Binding::Mouse(_, button) => {
+                let name = MOUSE_BUTTONS.iter().find(|b| b.0 == button).map(|b| b.1).unwrap_or("Unknown");
+                write!(f, "Mouse {}", name)
+            },
+            Binding::Button(_, button) => write!(f, "Pad {}", button.string()),
+            Binding::Axis(_, axis, direction) => {
+                write!(f, "Pad {}{}", axis.string(), if direction == AxisDirection::Positive { "+" } else { "-" })
+            }
+        }
+    }
+}
+
+///! Bindings without a state are Pressed
+impl FromStr for Binding {
+    type Err = BindingError;
+
+    fn from_str(text: &str) -> Result<Binding, BindingError> {
+        Binding::parse(text, BindingState::Pressed)
+    }
+}
+
+//mouse and gamepad inputs that are down
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash)]
+enum Control {
+    Mouse(MouseButton),
+    Button(Button),
+    Axis(Axis, AxisDirection)
+}
+
+//old bindings, new bindings and the command, queued by config change handlers
+type Rebind<T> = (Vec<Binding>, Vec<Binding>, T);
+
 pub struct Input<T> {
     binding: HashMap<Binding, T>,
+    rebinds: Rc<RefCell<Vec<Rebind<T>>>>,
     command_buffer: Vec<T>,
     keys: HashSet<Keycode>,
     old_keys: HashSet<Keycode>,
+    controls: HashSet<Control>,
+    old_controls: HashSet<Control>,
 }
 
 impl<T> Input<T> where T: Sized + Clone {
@@ -28,17 +205,23 @@ impl<T> Input<T> where T: Sized + Clone {
         if keymap.is_some() {
             Input {
                 binding: keymap.unwrap(),
+                rebinds: Rc::new(RefCell::new(Vec::new())),
                 command_buffer: Vec::<T>::new(),
                 keys: HashSet::<Keycode>::new(),
                 old_keys: HashSet::<Keycode>::new(),
+                controls: HashSet::new(),
+                old_controls: HashSet::new(),
             }
         }
         else {
             Input{
                 binding: HashMap::<Binding, T>::new(),
+                rebinds: Rc::new(RefCell::new(Vec::new())),
                 command_buffer: Vec::<T>::new(),
                 keys: HashSet::<Keycode>::new(),
                 old_keys: HashSet::<Keycode>::new(),
+                controls: HashSet::new(),
+                old_controls: HashSet::new(),
             }
         }
     }
@@ -49,28 +232,93 @@ impl<T> Input<T> where T: Sized + Clone {
         self
     }
 
+    //swap in bindings whose config keys changed since the last update
+    fn apply_rebinds(&mut self) {
+        for (old, new, c) in self.rebinds.borrow_mut().drain(..) {
+            for b in &old {
+                self.binding.remove(b);
+            }
+            for b in new {
+                self.binding.insert(b, c.clone());
+            }
+        }
+    }
+
     pub fn clear_commands(&mut self) {
         self.command_buffer.clear();
     }
 
+    ///! Bind c to b, returning what b was bound to before
+    pub fn bind(&mut self, b: Binding, c: T) -> Option<T> {
+        self.binding.insert(b, c)
+    }
+
+    pub fn unbind(&mut self, b: &Binding) -> Option<T> {
+        self.binding.remove(b)
+    }
+
+    pub fn bindings(&self) -> &HashMap<Binding, T> {
+        &self.binding
+    }
+
+    ///! Mouse buttons for this frame. Call it before `key_state`, which fires the bindings
+    ///! and keeps this frame's buttons for telling Held and Released apart next frame.
+    pub fn mouse_state(&mut self, mouse: MouseState) {
+        self.controls.retain(|c| !matches!(*c, Control::Mouse(_)));
+        self.controls.extend(mouse.pressed_mouse_buttons().map(Control::Mouse));
+    }
+
+    ///! Buttons and axes of the gamepad to read bindings from this frame. Call it before `key_state`,
+    ///! which fires the bindings and keeps this frame's controls for telling Held and Released apart next frame.
+    pub fn controller_state(&mut self, pad: &GameController) {
+        self.controls.retain(|c| matches!(*c, Control::Mouse(_)));
+        for &button in PAD_BUTTONS.iter() {
+            if pad.button(button) {
+                self.controls.insert(Control::Button(button));
+            }
+        }
+        for &axis in PAD_AXES.iter() {
+            match pad.axis(axis) {
+                v if v > AXIS_THRESHOLD => { self.controls.insert(Control::Axis(axis, AxisDirection::Positive)); },
+                v if v < -AXIS_THRESHOLD => { self.controls.insert(Control::Axis(axis, AxisDirection::Negative)); },
+                _ => {}
+            }
+        }
+    }
+
+    //whether binding goes off this frame
+    fn fires(&self, binding: &Binding, mods: Modifiers, old_mods: Modifiers) -> bool {
+        let control = |c: Control| (self.controls.contains(&c), self.old_controls.contains(&c));
+        let (now, before) = match *binding {
+            Binding::Key(_, kc) => (self.keys.contains(&kc), self.old_keys.contains(&kc)),
+            Binding::Combo(_, m, kc) => (m == mods && self.keys.contains(&kc), m == old_mods && self.old_keys.contains(&kc)),
+            Binding::Mouse(_, button) => control(Control::Mouse(button)),
+            Binding::Button(_, button) => control(Control::Button(button)),
+            Binding::Axis(_, axis, direction) => control(Control::Axis(axis, direction))
+        };
+        match *binding.state() {
+            BindingState::Pressed => now && !before,
+            BindingState::Held => now && before,
+            BindingState::Released => !now && before
+        }
+    }
+
+    ///! Keys for this frame, then fire every binding. Call it once a frame, last, after `mouse_state`
+    ///! and `controller_state`: it also makes this frame's mouse and pad controls the previous ones,
+    ///! so updating them afterwards breaks Held and Released for their bindings.
     pub fn key_state<'a>(&mut self, keys: KeyboardState<'a>) {
         self.clear_commands();
+        self.apply_rebinds();
         self.old_keys = self.keys.clone();
         self.keys = keys.pressed_scancodes().filter_map(Keycode::from_scancode).collect();
-        // held keys (exist in old and new)
-        for key in self.keys.intersection(&self.old_keys) {
-            let command = self.binding.get(&Binding::Key(BindingState::Held, key.clone()));
-            if command.is_none() { continue }
-
-            self.command_buffer.push(command.unwrap().clone());
-        }
-
-        for key in &self.keys - &self.old_keys {
-            let command = self.binding.get(&Binding::Key(BindingState::Pressed, key.clone()));
-            if command.is_none() { continue }
 
-            self.command_buffer.push(command.unwrap().clone());
-        }
+        let (mods, old_mods) = (Modifiers::held(&self.keys), Modifiers::held(&self.old_keys));
+        let fired:Vec<T> = self.binding.iter()
+            .filter(|&(b, _)| self.fires(b, mods, old_mods))
+            .map(|(_, c)| c.clone())
+            .collect();
+        self.command_buffer.extend(fired);
+        self.old_controls = self.controls.clone();
     }
 
     pub fn key_pressed(&self, keycode: Keycode) -> bool {
@@ -85,3 +333,112 @@ impl<T> Input<T> where T: Sized + Clone {
         self.command_buffer.iter()
     }
 }
+
+#[cfg(feature = "config")]
+impl<T> Input<T> where T: Sized + Clone + 'static {
+    ///! Bind c to the binding or array of bindings in config key name, following them when the config changes
+    pub fn add_config_binding(mut self, config: &mut Config, name: &str, state: BindingState, c: T) -> Self {
+        let bindings = config.keybindings(name, state.clone());
+        let rebinds = Rc::downgrade(&self.rebinds);
+        let current = RefCell::new(bindings.clone());
+        let command = c.clone();
+        config.on_change(name, Box::new(move |_, value| {
+            let rebinds = match rebinds.upgrade() {
+                Some(rebinds) => rebinds,
+                None => return
+            };
+            let new = value.and_then(|v| parse_bindings(v, state.clone()).ok()).unwrap_or_default();
+            let old = current.replace(new.clone());
+            if old != new {
+                rebinds.borrow_mut().push((old, new, command.clone()));
+            }
+        }));
+        for b in bindings {
+            self.binding.insert(b, c.clone());
+        }
+        self
+    }
+}
+
+#[cfg(feature = "config")]
+impl<T> Input<T> where T: Sized + Clone + fmt::Display + FromStr {
+    ///! Write every binding into section of the user layer, one key per command.
+    ///! Commands with more than one binding get an array, commands the config has but nothing
+    ///! is bound to anymore get an empty one so the game and defaults layers don't bind them again.
+    pub fn save_bindings(&self, config: &mut Config, section: &str) -> Result<(), ConfigError> {
+        let mut commands:HashMap<String, Vec<String>> = HashMap::new();
+        for (binding, command) in &self.binding {
+            commands.entry(command.to_string()).or_default().push(binding.to_string());
+        }
+
+        let mut table = Table::new();
+        for command in config.keys(section) {
+            if !commands.contains_key(command) {
+                table.insert(command.to_string(), Value::Array(Vec::new()));
+            }
+        }
+        for (command, mut bindings) in commands {
+            bindings.sort();
+            let value = match bindings.len() {
+                1 => Value::String(bindings.remove(0)),
+                _ => Value::Array(bindings.into_iter().map(Value::String).collect())
+            };
+            table.insert(command, value);
+        }
+        config.set(section, Value::Table(table))
+    }
+
+    ///! Replace every binding with the ones in section, keys are commands parsed with `FromStr`
+    pub fn load_bindings(&mut self, config: &Config, section: &str) -> Result<(), ConfigError> {
+        self.binding = config.bindings::<T>(section)?.into_iter().map(|(c, b)| (b, c)).collect();
+        self.rebinds.borrow_mut().clear();
+        Ok(())
+    }
+}
+
+#[cfg(feature = "config")]
+#[test]
+fn config_binding() {
+    let mut c = Config::parse("[keybind]\nForward = \"W\"\n").unwrap();
+    let mut input = Input::new(None).add_config_binding(&mut c, "keybind.Forward", BindingState::Held, "Forward".to_string());
+    assert_eq!(input.bindings().len(), 1);
+
+    //saving a second binding turns the key into an array, which the handler follows
+    input.bind(Binding::Key(BindingState::Held, Keycode::Up), "Forward".to_string());
+    input.save_bindings(&mut c, "keybind").unwrap();
+    input.apply_rebinds();
+    assert_eq!(input.bindings().len(), 2);
+    assert!(input.bindings().contains_key(&Binding::Key(BindingState::Held, Keycode::W)));
+
+    c.set("keybind.Forward", config_array!["Space", "Pressed Return"]).unwrap();
+    input.apply_rebinds();
+    let mut bound:Vec<String> = input.bindings().keys().map(|b| b.to_string()).collect();
+    bound.sort();
+    assert_eq!(bound, vec!["Held Space", "Pressed Return"]);
+
+    c.remove("keybind.Forward");
+    input.apply_rebinds();
+    assert!(input.bindings().is_empty());
+}
+
+#[test]
+fn binding_text() {
+    let text = ["Held W", "Pressed Ctrl+Shift+S", "Released Mouse Left", "Pressed Pad a", "Held Pad leftx-", "Held Pad righttrigger+"];
+    for t in &text {
+        assert_eq!(t.parse::<Binding>().unwrap().to_string(), *t);
+    }
+
+    assert_eq!(Binding::parse("Space", BindingState::Held).unwrap(), Binding::Key(BindingState::Held, Keycode::Space));
+    assert_eq!(Binding::parse("released alt+ctrl+Return", BindingState::Held).unwrap(),
+               Binding::Combo(BindingState::Released, Modifiers { ctrl: true, shift: false, alt: true }, Keycode::Return));
+    assert_eq!("Mouse x2".parse::<Binding>().unwrap(), Binding::Mouse(BindingState::Pressed, MouseButton::X2));
+    assert_eq!("Left Ctrl".parse::<Binding>().unwrap(), Binding::Key(BindingState::Pressed, Keycode::LCtrl));
+
+    match "Ctrl+Nope".parse::<Binding>() {
+        Err(BindingError::UnknownKey(ref k)) if k == "Nope" => {},
+        other => panic!("{:?}", other)
+    }
+    assert!("Mouse Wheel".parse::<Binding>().is_err());
+    assert!("Pad leftx".parse::<Binding>().is_err());
+    assert!("Held ".parse::<Binding>().is_err());
+}
@@ -7,8 +7,19 @@ extern crate glium_sdl2;
 extern crate sdl2;
 #[cfg(feature = "config")]
 extern crate toml;
+#[cfg(feature = "config")]
+extern crate toml_edit;
+#[cfg(feature = "config")]
+extern crate serde_path_to_error;
 #[cfg(feature = "collision")]
 extern crate cgmath;
+#[cfg(feature = "parallel")]
+extern crate rayon;
+#[cfg(any(feature = "serde", feature = "config"))]
+#[macro_use]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
 #[macro_use]
 extern crate thiserror;
 #[macro_use]
@@ -18,6 +29,7 @@ mod window;
 #[cfg(feature = "window")]
 mod input;
 mod storage;
+mod dirs;
 mod cgmath_augment;
 #[cfg(feature = "config")]
 pub mod config;
@@ -29,4 +41,5 @@ pub use window::*;
 #[cfg(feature = "window")]
 pub use input::*;
 pub use storage::*;
+pub use dirs::*;
 
@@ -9,6 +9,20 @@ macro_rules! main_try {
     })
 }
 
+///! A mount serving files under dir, relative to the crate root.
+///! Release builds compile the listed files into the binary, debug builds read dir from disk.
+#[macro_export]
+macro_rules! embed_assets {
+    ($dir:expr, [$($file:expr),* $(,)*]) => ({
+        #[cfg(debug_assertions)]
+        let mount = $crate::Directory::new(concat!(env!("CARGO_MANIFEST_DIR"), "/", $dir));
+        #[cfg(not(debug_assertions))]
+        let mount = $crate::Memory::new()
+            $(.with_file($file, &include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/", $dir, "/", $file))[..]))*;
+        mount
+    })
+}
+
 macro_rules! println_err {
     ($($arg:tt)*) => (
         use std::io::Write;
@@ -38,3 +52,29 @@ macro_rules! config_bool {
         $crate::config::Value::Boolean($expr as bool)
         )
 }
+
+#[macro_export]
+macro_rules! config_float {
+    ($expr:expr) => (
+        $crate::config::Value::Float($expr as f64)
+        )
+}
+
+///! `config_array![1, 2, config_float!(3)]`, anything that converts into a Value goes
+#[macro_export]
+macro_rules! config_array {
+    ($($expr:expr),* $(,)*) => (
+        $crate::config::Value::Array(vec![$($crate::config::Value::from($expr)),*])
+        )
+}
+
+///! `config_table!{"width" => 1280, "title" => "pipewrench"}`
+#[macro_export]
+macro_rules! config_table {
+    ($($key:expr => $expr:expr),* $(,)*) => ({
+        #[allow(unused_mut)]
+        let mut table = $crate::config::Table::new();
+        $(table.insert($key.to_string(), $crate::config::Value::from($expr));)*
+        $crate::config::Value::Table(table)
+    })
+}
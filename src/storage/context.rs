@@ -0,0 +1,118 @@
+use std::any::TypeId;
+use std::io::{self, Read};
+use std::path::Path;
+use super::{AssetServer, Handle, StorageError, Vfs, Reader};
+
+///! An asset type and path, what the dependency graph is made of
+#[derive(Debug,Clone,PartialEq,Eq,Hash)]
+pub struct AssetKey {
+    pub type_id: TypeId,
+    pub path: String
+}
+
+impl AssetKey {
+    pub fn of<T: 'static>(path: &str) -> AssetKey {
+        AssetKey {
+            type_id: TypeId::of::<T>(),
+            path: path.to_string()
+        }
+    }
+}
+
+///! What a loader gets handed, the asset path and a reader over its bytes.
+///! Nothing is opened until the loader actually reads.
+pub struct LoadContext<'a> {
+    path: &'a str,
+    vfs: &'a Vfs,
+    reader: Option<Reader>,
+    server: Option<&'a mut AssetServer>,
+    dependencies: Vec<AssetKey>
+}
+
+impl<'a> LoadContext<'a> {
+    pub fn new(vfs: &'a Vfs, path: &'a str) -> LoadContext<'a> {
+        LoadContext {
+            path,
+            vfs,
+            reader: None,
+            server: None,
+            dependencies: Vec::new()
+        }
+    }
+
+    ///! Let the loader pull in dependencies through server
+    pub fn with_server(mut self, server: &'a mut AssetServer) -> Self {
+        self.server = Some(server);
+        self
+    }
+
+    pub fn path(&self) -> &str {
+        self.path
+    }
+
+    pub fn extension(&self) -> Option<&str> {
+        Path::new(self.path).extension().and_then(|e| e.to_str())
+    }
+
+    pub fn vfs(&self) -> &Vfs {
+        self.vfs
+    }
+
+    pub fn read_bytes(&mut self) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub fn read_string(&mut self) -> io::Result<String> {
+        let mut text = String::new();
+        self.read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    ///! Load an asset this one depends on, so reloading or dropping it cascades to us.
+    ///! Only works for loads going through an `AssetServer`.
+    pub fn load<D: 'static>(&mut self, path: &str) -> Result<Handle<D>, StorageError> {
+        let handle = match self.server {
+            Some(ref mut server) => server.load::<D>(path)?,
+            None => return Err(StorageError::NoLoader(path.to_string()))
+        };
+        self.dependencies.push(AssetKey::of::<D>(path));
+        Ok(handle)
+    }
+
+    ///! Everything loaded through `load` so far
+    pub fn dependencies(&self) -> &[AssetKey] {
+        &self.dependencies
+    }
+
+    pub fn into_dependencies(self) -> Vec<AssetKey> {
+        self.dependencies
+    }
+}
+
+impl<'a> Read for LoadContext<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.reader.is_none() {
+            self.reader = Some(self.vfs.open(self.path)?);
+        }
+        self.reader.as_mut().unwrap().read(buf)
+    }
+}
+
+#[test]
+fn load_context() {
+    use super::Memory;
+
+    let vfs = Vfs::new().with_mount(Memory::new().with_file("a.txt", &b"text"[..]));
+    let mut ctx = LoadContext::new(&vfs, "a.txt");
+    assert_eq!(ctx.extension(), Some("txt"));
+    assert_eq!(ctx.read_string().unwrap(), "text");
+
+    //no server to resolve dependencies with
+    assert!(ctx.load::<String>("b.txt").is_err());
+    assert!(ctx.dependencies().is_empty());
+
+    let mut missing = LoadContext::new(&vfs, "b.txt");
+    assert_eq!(missing.read_bytes().unwrap_err().kind(), io::ErrorKind::NotFound);
+}
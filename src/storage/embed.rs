@@ -0,0 +1,61 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+///! Every file under dir, relative to it with / separators and sorted
+pub fn asset_files<P: AsRef<Path>>(dir: P) -> io::Result<Vec<String>> {
+    fn walk(root: &Path, dir: &Path, files: &mut Vec<String>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                walk(root, &path, files)?;
+                continue
+            }
+            files.push(path.strip_prefix(root).unwrap().components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect::<Vec<String>>()
+                .join("/"));
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    walk(dir.as_ref(), dir.as_ref(), &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+///! For build scripts, writes an `embed_assets!` call for everything under dir to out.
+///! dir is relative to the crate root, pull the result in with
+///! `include!(concat!(env!("OUT_DIR"), "/assets.rs"))`.
+pub fn write_embedded<P: AsRef<Path>>(dir: &str, out: P) -> io::Result<()> {
+    let root = Path::new(&std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default()).join(dir);
+    let files = asset_files(&root)?;
+
+    let mut f = fs::File::create(out)?;
+    writeln!(f, "embed_assets!({:?}, [", dir)?;
+    for file in &files {
+        writeln!(f, "    {:?},", file)?;
+    }
+    writeln!(f, "])")?;
+
+    println!("cargo:rerun-if-changed={}", root.display());
+    Ok(())
+}
+
+#[test]
+fn embedded_assets() {
+    use super::Vfs;
+
+    //tests are debug builds, so this reads straight from the crate's examples directory
+    let vfs = Vfs::new().with_mount(embed_assets!("examples", ["config.toml"]));
+    let text = String::from_utf8(vfs.read("config.toml").unwrap()).unwrap();
+    assert!(text.contains("[keybind]"));
+
+    let dir = std::env::temp_dir().join("pipewrench_embedded_assets");
+    fs::create_dir_all(dir.join("gfx")).unwrap();
+    fs::write(dir.join("gfx/player.png"), b"png").unwrap();
+    fs::write(dir.join("readme.txt"), b"text").unwrap();
+    assert_eq!(asset_files(&dir).unwrap(), vec!["gfx/player.png", "readme.txt"]);
+    fs::remove_dir_all(&dir).unwrap();
+}
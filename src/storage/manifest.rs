@@ -0,0 +1,222 @@
+use std::collections::{BTreeMap, HashMap};
+use config::{Config, ConfigError, Table, Value};
+use super::{Handle, Storage, StorageError};
+
+#[derive(Debug, Error)]
+pub enum ManifestError {
+    #[error("Manifest entry \"{0}\" isn't a path")]
+    NotAPath(String),
+    #[error("Manifest has no group named \"{0}\"")]
+    NoGroup(String),
+    #[error("Error reading manifest: {source}")]
+    ConfigError {
+        #[from]
+        source: ConfigError
+    },
+    #[error("Error preloading group: {source}")]
+    StorageError {
+        #[from]
+        source: StorageError
+    }
+}
+
+///! Groups of assets by logical name, read from toml.
+///! Every table is a group, `[textures] player = "gfx/player.png"` makes group `textures`
+///! with `player` in it, nested tables make groups like `level1.textures`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Manifest {
+    groups: BTreeMap<String, BTreeMap<String, String>>
+}
+
+impl Manifest {
+    pub fn new() -> Manifest {
+        Manifest::default()
+    }
+
+    pub fn from_file(path: &str) -> Result<Manifest, ManifestError> {
+        Manifest::from_config(&Config::from_file(path)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Manifest, ManifestError> {
+        Manifest::from_config(&Config::parse(text)?)
+    }
+
+    pub fn from_config(config: &Config) -> Result<Manifest, ManifestError> {
+        let mut manifest = Manifest::new();
+        manifest.read_table("", config.table())?;
+        Ok(manifest)
+    }
+
+    fn read_table(&mut self, prefix: &str, table: &Table) -> Result<(), ManifestError> {
+        for (key, value) in table {
+            let name = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+            match *value {
+                Value::Table(ref t) => self.read_table(&name, t)?,
+                Value::String(ref path) if !prefix.is_empty() => {
+                    self.groups.entry(prefix.to_string()).or_default().insert(key.clone(), path.clone());
+                },
+                _ => return Err(ManifestError::NotAPath(name))
+            }
+        }
+        Ok(())
+    }
+
+    ///! Add name to group, replacing whatever path it had
+    pub fn insert(&mut self, group: &str, name: &str, path: &str) {
+        self.groups.entry(group.to_string()).or_default().insert(name.to_string(), path.to_string());
+    }
+
+    pub fn groups(&self) -> Vec<&str> {
+        self.groups.keys().map(|k| &k[..]).collect()
+    }
+
+    ///! Logical name to path for every asset in group
+    pub fn group(&self, group: &str) -> Option<&BTreeMap<String, String>> {
+        self.groups.get(group)
+    }
+
+    pub fn path(&self, group: &str, name: &str) -> Option<&str> {
+        self.groups.get(group)?.get(name).map(|p| &p[..])
+    }
+
+    ///! Load every asset in group into storage.
+    ///! If any of them fails the ones loaded so far are dropped again.
+    pub fn preload<T>(&self, group: &str, storage: &mut Storage<T>) -> Result<AssetGroup<T>, ManifestError> {
+        let entries = self.group(group).ok_or_else(|| ManifestError::NoGroup(group.to_string()))?;
+        let mut loaded = AssetGroup {
+            name: group.to_string(),
+            handles: HashMap::new(),
+            counted: Vec::new()
+        };
+
+        for (name, path) in entries {
+            //assets loaded outside any group are left alone
+            let counted = !storage.has(path) || storage.groups.contains_key(path);
+            match storage.load(path) {
+                Ok(handle) => {
+                    loaded.handles.insert(name.clone(), handle);
+                    if counted {
+                        *storage.groups.entry(path.clone()).or_insert(0) += 1;
+                        loaded.counted.push(path.clone());
+                    }
+                },
+                Err(e) => {
+                    loaded.unload(storage);
+                    return Err(ManifestError::from(e))
+                }
+            }
+        }
+        Ok(loaded)
+    }
+}
+
+///! A preloaded manifest group, look assets up by logical name and unload them together
+#[derive(Debug)]
+pub struct AssetGroup<T> {
+    name: String,
+    handles: HashMap<String, Handle<T>>,
+    //paths this group holds a count on in the storage, the last group holding one drops it
+    counted: Vec<String>
+}
+
+impl<T> AssetGroup<T> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get(&self, name: &str) -> Option<Handle<T>> {
+        self.handles.get(name).cloned()
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.handles.keys().map(|k| &k[..]).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.handles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+
+    ///! Drop every asset of this group that no other preloaded group still holds
+    pub fn unload(self, storage: &mut Storage<T>) {
+        for path in self.counted {
+            let last = match storage.groups.get_mut(&path) {
+                Some(count) => {
+                    *count -= 1;
+                    *count == 0
+                },
+                None => false
+            };
+            if last {
+                storage.drop_path(&path);
+            }
+        }
+    }
+}
+
+#[test]
+fn manifest_groups() {
+    use std::sync::Arc;
+    use super::{Memory, Vfs};
+
+    let manifest = Manifest::parse(r#"
+        [textures]
+        player = "gfx/player.png"
+        enemy = "gfx/enemy.png"
+
+        [level1.textures]
+        player = "gfx/player.png"
+        tiles = "gfx/level1.png"
+
+        [broken]
+        missing = "gfx/missing.png"
+    "#).unwrap();
+    assert_eq!(manifest.groups(), vec!["broken", "level1.textures", "textures"]);
+    assert_eq!(manifest.path("level1.textures", "tiles"), Some("gfx/level1.png"));
+
+    let vfs = Memory::new()
+        .with_file("gfx/player.png", &b"player"[..])
+        .with_file("gfx/enemy.png", &b"enemy"[..])
+        .with_file("gfx/level1.png", &b"tiles"[..]);
+    let mut store = Storage::<String>::new(Box::new(|ctx| Ok(ctx.read_string()?)))
+        .with_vfs(Arc::new(Vfs::new().with_mount(vfs)));
+
+    let common = manifest.preload("textures", &mut store).unwrap();
+    let level = manifest.preload("level1.textures", &mut store).unwrap();
+    assert_eq!(*store.get(level.get("tiles").unwrap()).unwrap(), "tiles");
+    assert_eq!(level.get("player"), common.get("player"));
+    assert_eq!(level.len(), 2);
+
+    //the player texture is still held by the common group and survives the level
+    level.unload(&mut store);
+    assert!(!store.has("gfx/level1.png"));
+    assert!(store.get(common.get("player").unwrap()).is_ok());
+
+    //whichever group loaded it first, it goes with the last group holding it
+    let level = manifest.preload("level1.textures", &mut store).unwrap();
+    common.unload(&mut store);
+    assert!(!store.has("gfx/enemy.png"));
+    assert!(store.get(level.get("player").unwrap()).is_ok());
+    level.unload(&mut store);
+    assert!(!store.has("gfx/player.png"));
+
+    //assets loaded outside any group are never dropped by one
+    let outside = store.load("gfx/enemy.png").unwrap();
+    manifest.preload("textures", &mut store).unwrap().unload(&mut store);
+    assert!(store.get(outside).is_ok());
+    assert!(!store.has("gfx/player.png"));
+
+    match manifest.preload("broken", &mut store) {
+        Err(ManifestError::StorageError { .. }) => {},
+        _ => panic!("broken group has a missing file")
+    }
+    assert!(manifest.preload("nope", &mut store).is_err());
+
+    match Manifest::parse("[textures]\nplayer = 5") {
+        Err(ManifestError::NotAPath(ref k)) if k == "textures.player" => {},
+        _ => panic!("integers aren't paths")
+    }
+}
@@ -0,0 +1,999 @@
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::mpsc::{channel, Sender, Receiver};
+use std::time::SystemTime;
+
+mod vfs;
+mod pack;
+mod context;
+mod server;
+mod sync;
+mod embed;
+#[cfg(feature = "config")]
+mod manifest;
+
+pub use self::vfs::*;
+pub use self::pack::*;
+pub use self::context::*;
+pub use self::server::*;
+pub use self::sync::*;
+pub use self::embed::*;
+#[cfg(feature = "config")]
+pub use self::manifest::*;
+
+pub trait SizedError: std::error::Error + Sized {}
+
+#[derive(Debug,Error)]
+pub enum StorageError {
+    #[error("Storage already has key named \"{0}\"")]
+    StorageOccupied(String),
+    #[error("Nothing is loading \"{0}\"")]
+    NotLoading(String),
+    #[error("Handle is stale, the asset it pointed to was dropped")]
+    StaleHandle,
+    #[error("\"{0}\" was evicted, load it again")]
+    Evicted(String),
+    #[error("No loader registered for \"{0}\"")]
+    NoLoader(String),
+    #[error("Dependency cycle: {0}")]
+    DependencyCycle(String),
+    #[error("Error: {source}")]
+    Error {
+        #[from]
+        source: BoxedError
+    }
+}
+
+///! Errors loaders return, Send + Sync so loads can fail on any thread
+pub type BoxedError = Box<dyn std::error::Error + Send + Sync>;
+
+///! Turns the bytes behind a path into an asset, the same loader works for files, packs and embedded bytes
+pub type LoaderFunction<T>= dyn Fn(&mut LoadContext) -> Result<T, BoxedError> + Send + Sync;
+
+///! Called with the path, handle and new value after a hot reload swapped an asset
+pub type Subscriber<T> = dyn Fn(&str, Handle<T>, &Rc<T>);
+
+///! Index and generation of an asset in a Storage, dropping the asset makes old handles stale
+pub struct Handle<T> {
+    index: u32,
+    generation: u32,
+    _marker: PhantomData<fn() -> T>
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Handle<T> {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Handle<T>) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Handle({}v{})", self.index, self.generation)
+    }
+}
+
+///! Roughly how many bytes an asset keeps alive, used for `Eviction::Lru` budgets
+pub trait AssetSize {
+    fn size_bytes(&self) -> usize;
+}
+
+impl AssetSize for String {
+    fn size_bytes(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T> AssetSize for Vec<T> {
+    fn size_bytes(&self) -> usize {
+        self.capacity() * mem::size_of::<T>()
+    }
+}
+
+fn shallow_size<T>(_: &T) -> usize {
+    mem::size_of::<T>()
+}
+
+///! When a Storage lets go of assets on its own
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Eviction {
+    ///! Keep everything until it's dropped, the default
+    Manual,
+    ///! `evict` drops assets nobody outside the Storage holds an Rc to
+    Unused,
+    ///! Drop the least recently used unheld assets whenever resident bytes go over the budget
+    Lru(usize),
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Default)]
+pub struct StorageStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub evictions: usize,
+    pub resident_bytes: usize
+}
+
+struct Slot<T> {
+    path: String,
+    generation: u32,
+    //None once evicted
+    value: Option<Rc<T>>,
+    //bumped on every reload
+    version: usize,
+    size: usize,
+    last_used: Cell<u64>,
+    //holding the fallback after a failed load
+    fallback: bool,
+}
+
+///! A load that failed and got the fallback value instead, see `Storage::with_fallback`
+#[derive(Debug,Clone,PartialEq)]
+pub struct LoadFailure {
+    pub path: String,
+    pub error: String
+}
+
+///! A load running on a worker thread, see `Storage::load_async`
+#[derive(Debug)]
+pub struct Pending<T> {
+    path: String,
+    _marker: PhantomData<fn() -> T>
+}
+
+impl<T> Pending<T> {
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+///! Background load counts since the last `reset_progress`
+#[derive(Debug,Clone,Copy,PartialEq,Default)]
+pub struct LoadProgress {
+    pub requested: usize,
+    pub loaded: usize,
+    pub failed: usize
+}
+
+impl LoadProgress {
+    ///! 0.0 to 1.0, for loading bars
+    pub fn fraction(&self) -> f32 {
+        if self.requested == 0 {
+            return 1.0
+        }
+        (self.loaded + self.failed) as f32 / self.requested as f32
+    }
+
+    pub fn done(&self) -> bool {
+        self.loaded + self.failed >= self.requested
+    }
+}
+
+type LoadResult<T> = (String, Result<T, StorageError>);
+
+type Job = Box<dyn FnOnce() + Send>;
+
+//most threads background loads get when there's no rayon pool to share
+#[cfg(not(feature = "parallel"))]
+const MAX_WORKERS: usize = 4;
+
+//background loads share a few threads instead of getting one each, the rest queue up
+#[cfg(feature = "parallel")]
+fn spawn_load(job: Job) {
+    rayon::spawn(job);
+}
+
+#[cfg(not(feature = "parallel"))]
+fn spawn_load(job: Job) {
+    use std::sync::{Mutex, OnceLock};
+    use std::thread;
+
+    static JOBS: OnceLock<Sender<Job>> = OnceLock::new();
+    let jobs = JOBS.get_or_init(|| {
+        let (sender, receiver) = channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = thread::available_parallelism().map(|n| n.get()).unwrap_or(1).min(MAX_WORKERS);
+        for _ in 0..workers {
+            let receiver = receiver.clone();
+            thread::spawn(move || loop {
+                let job = receiver.lock().unwrap().recv();
+                match job {
+                    Ok(job) => job(),
+                    Err(_) => return
+                }
+            });
+        }
+        sender
+    });
+    //the workers never exit while the sender is alive
+    let _ = jobs.send(job);
+}
+
+pub struct Storage<T> {
+    slots: Vec<Slot<T>>,
+    paths: HashMap<String, u32>,
+    free: Vec<u32>,
+    loader: Arc<LoaderFunction<T>>,
+    vfs: Arc<Vfs>,
+    //last seen modification time of every loaded path, None when hot reload is off
+    watched: Option<HashMap<String, Option<SystemTime>>>,
+    subscribers: Vec<Box<Subscriber<T>>>,
+    loading: HashSet<String>,
+    failed: HashMap<String, StorageError>,
+    progress: LoadProgress,
+    sender: Sender<LoadResult<T>>,
+    receiver: Receiver<LoadResult<T>>,
+    eviction: Eviction,
+    sizer: fn(&T) -> usize,
+    clock: Cell<u64>,
+    stats: Cell<StorageStats>,
+    fallback: Option<Rc<T>>,
+    errors: Vec<LoadFailure>,
+    //how many preloaded manifest groups hold each path a group loaded
+    groups: HashMap<String, usize>,
+}
+
+impl<T> Storage<T> where T: Sized {
+    pub fn new(loader: Box<LoaderFunction<T>>) -> Storage<T> {
+        let (sender, receiver) = channel();
+        Storage {
+            slots: Vec::new(),
+            paths: HashMap::new(),
+            free: Vec::new(),
+            loader: Arc::from(loader),
+            vfs: Arc::new(Vfs::native()),
+            watched: None,
+            subscribers: Vec::new(),
+            loading: HashSet::new(),
+            failed: HashMap::new(),
+            progress: LoadProgress::default(),
+            sender,
+            receiver,
+            eviction: Eviction::Manual,
+            sizer: shallow_size::<T>,
+            clock: Cell::new(0),
+            stats: Cell::new(StorageStats::default()),
+            fallback: None,
+            errors: Vec::new(),
+            groups: HashMap::new(),
+        }
+    }
+
+    ///! Read assets through vfs instead of straight from the working directory
+    pub fn with_vfs(mut self, vfs: Arc<Vfs>) -> Self {
+        self.vfs = vfs;
+        self
+    }
+
+    pub fn vfs(&self) -> &Arc<Vfs> {
+        &self.vfs
+    }
+
+    pub fn with_eviction(mut self, eviction: Eviction) -> Self {
+        self.eviction = eviction;
+        self
+    }
+
+    ///! Hand out fallback instead of failing when the loader errors, failures are logged and kept in `errors`
+    pub fn with_fallback(mut self, fallback: T) -> Self {
+        self.set_fallback(Some(fallback));
+        self
+    }
+
+    pub fn set_fallback(&mut self, fallback: Option<T>) {
+        self.fallback = fallback.map(Rc::new);
+    }
+
+    ///! Every load that fell back since the last `clear_errors`
+    pub fn errors(&self) -> &[LoadFailure] {
+        &self.errors
+    }
+
+    pub fn clear_errors(&mut self) {
+        self.errors.clear();
+    }
+
+    ///! Measure assets with `AssetSize` instead of just their stack size
+    pub fn with_asset_size(mut self) -> Self where T: AssetSize {
+        self.sizer = <T as AssetSize>::size_bytes;
+        self
+    }
+
+    pub fn stats(&self) -> StorageStats {
+        self.stats.get()
+    }
+
+    fn run_loader(&self, path: &str) -> Result<T, StorageError> {
+        Ok((self.loader)(&mut LoadContext::new(&self.vfs, path))?)
+    }
+
+    fn count<F: FnOnce(&mut StorageStats)>(&self, f: F) {
+        let mut stats = self.stats.get();
+        f(&mut stats);
+        self.stats.set(stats);
+    }
+
+    fn touch(&self, slot: &Slot<T>) {
+        let now = self.clock.get() + 1;
+        self.clock.set(now);
+        slot.last_used.set(now);
+    }
+
+    fn handle_at(&self, index: u32) -> Handle<T> {
+        Handle {
+            index,
+            generation: self.slots[index as usize].generation,
+            _marker: PhantomData
+        }
+    }
+
+    //path must not be stored yet
+    fn insert(&mut self, path: &str, data: T) -> Handle<T> {
+        let index = self.reserve(path);
+        self.fill(index, data);
+        self.handle_at(index)
+    }
+
+    //empty slot for path, which must not be stored yet
+    fn reserve(&mut self, path: &str) -> u32 {
+        let index = match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.path = path.to_string();
+                slot.version = 0;
+                index
+            },
+            None => {
+                self.slots.push(Slot {
+                    path: path.to_string(),
+                    generation: 0,
+                    value: None,
+                    version: 0,
+                    size: 0,
+                    last_used: Cell::new(0),
+                    fallback: false,
+                });
+                (self.slots.len() - 1) as u32
+            }
+        };
+        self.paths.insert(path.to_string(), index);
+        index
+    }
+
+    //put data in a live slot, replacing whatever was there
+    fn fill(&mut self, index: u32, data: T) {
+        let size = (self.sizer)(&data);
+        self.fill_with(index, Rc::new(data), size, false);
+    }
+
+    fn fill_with(&mut self, index: u32, value: Rc<T>, size: usize, fallback: bool) {
+        let old = {
+            let slot = &mut self.slots[index as usize];
+            let old = if slot.value.is_some() { slot.size } else { 0 };
+            slot.value = Some(value);
+            slot.size = size;
+            slot.fallback = fallback;
+            old
+        };
+        self.count(|s| s.resident_bytes = s.resident_bytes - old + size);
+        self.touch(&self.slots[index as usize]);
+
+        if let Some(ref mut watched) = self.watched {
+            let path = &self.slots[index as usize].path;
+            watched.insert(path.clone(), self.vfs.modified(path));
+        }
+
+        if let Eviction::Lru(budget) = self.eviction {
+            self.shrink_to(budget, Some(index));
+        }
+    }
+
+    //only checks the generation, evicted slots are still valid
+    fn slot(&self, handle: Handle<T>) -> Result<&Slot<T>, StorageError> {
+        match self.slots.get(handle.index as usize) {
+            Some(slot) if slot.generation == handle.generation => Ok(slot),
+            _ => Err(StorageError::StaleHandle)
+        }
+    }
+
+    fn evict_slot(&mut self, index: u32) {
+        let size = {
+            let slot = &mut self.slots[index as usize];
+            slot.value = None;
+            if let Some(ref mut watched) = self.watched {
+                watched.remove(&slot.path);
+            }
+            slot.size
+        };
+        self.count(|s| {
+            s.resident_bytes -= size;
+            s.evictions += 1;
+        });
+    }
+
+    //evict unheld assets, least recently used first, until we fit in budget
+    fn shrink_to(&mut self, budget: usize, keep: Option<u32>) -> usize {
+        let mut candidates:Vec<(u64, u32)> = self.paths.values()
+            .filter(|&&i| Some(i) != keep)
+            .filter_map(|&i| {
+                let slot = &self.slots[i as usize];
+                match slot.value {
+                    Some(ref v) if Rc::strong_count(v) == 1 => Some((slot.last_used.get(), i)),
+                    _ => None
+                }
+            })
+            .collect();
+        candidates.sort();
+
+        let mut evicted = 0;
+        for (_, index) in candidates {
+            if self.stats.get().resident_bytes <= budget {
+                break
+            }
+            self.evict_slot(index);
+            evicted += 1;
+        }
+        evicted
+    }
+
+    ///! Apply the eviction policy now, returns how many assets were evicted
+    pub fn evict(&mut self) -> usize {
+        match self.eviction {
+            Eviction::Manual => 0,
+            Eviction::Unused => self.shrink_to(0, None),
+            Eviction::Lru(budget) => self.shrink_to(budget, None),
+        }
+    }
+
+    ///! consume data and return a handle to it
+    pub fn add(&mut self, path: &str, data: T) -> Result<Handle<T>, StorageError> {
+        if self.paths.contains_key(path) {
+            return Err(StorageError::StorageOccupied(path.to_string()))
+        }
+        Ok(self.insert(path, data))
+    }
+
+    pub fn load(&mut self, path: &str) -> Result<Handle<T>, StorageError> {
+        if let Some(handle) = self.handle(path) {
+            if self.slots[handle.index as usize].value.is_some() {
+                self.count(|s| s.hits += 1);
+                self.touch(&self.slots[handle.index as usize]);
+                return Ok(handle)
+            }
+            //evicted, load it back into the same slot so old handles keep working
+            self.count(|s| s.misses += 1);
+            let data = match self.run_loader(path) {
+                Ok(data) => data,
+                Err(e) => return self.fall_back(path, e)
+            };
+            self.fill(handle.index, data);
+            return Ok(handle)
+        }
+        self.count(|s| s.misses += 1);
+        match self.run_loader(path) {
+            Ok(data) => Ok(self.insert(path, data)),
+            Err(e) => self.fall_back(path, e)
+        }
+    }
+
+    //store the fallback for path if there is one, logging and keeping the error
+    fn fall_back(&mut self, path: &str, error: StorageError) -> Result<Handle<T>, StorageError> {
+        let value = match self.fallback {
+            Some(ref value) => value.clone(),
+            None => return Err(error)
+        };
+        println_err!("Failed to load {}, using the fallback: {}", path, error);
+        self.errors.push(LoadFailure {
+            path: path.to_string(),
+            error: error.to_string()
+        });
+
+        let index = match self.paths.get(path) {
+            Some(&index) => index,
+            None => self.reserve(path)
+        };
+        //shared between every failed path, so it doesn't count towards resident bytes
+        self.fill_with(index, value, 0, true);
+        Ok(self.handle_at(index))
+    }
+
+    ///! Is handle holding the fallback because its load failed
+    pub fn is_fallback(&self, handle: Handle<T>) -> Result<bool, StorageError> {
+        Ok(self.slot(handle)?.fallback)
+    }
+
+    ///! Look up the handle for an already stored path
+    pub fn handle(&self, path: &str) -> Option<Handle<T>> {
+        self.paths.get(path).map(|&index| self.handle_at(index))
+    }
+
+    pub fn has(&self, path: &str) -> bool {
+        self.paths.contains_key(path)
+    }
+
+    //stored and not evicted
+    fn resident(&self, path: &str) -> bool {
+        match self.paths.get(path) {
+            Some(&index) => self.slots[index as usize].value.is_some(),
+            None => false
+        }
+    }
+
+    pub fn get(&self, handle: Handle<T>) -> Result<Rc<T>, StorageError> {
+        let slot = self.slot(handle)?;
+        match slot.value {
+            Some(ref v) => {
+                self.count(|s| s.hits += 1);
+                self.touch(slot);
+                Ok(v.clone())
+            },
+            None => {
+                self.count(|s| s.misses += 1);
+                Err(StorageError::Evicted(slot.path.clone()))
+            }
+        }
+    }
+
+    pub fn path(&self, handle: Handle<T>) -> Result<&str, StorageError> {
+        Ok(&self.slot(handle)?.path)
+    }
+
+    ///! Bumped every time the asset is reloaded
+    pub fn version(&self, handle: Handle<T>) -> Result<usize, StorageError> {
+        Ok(self.slot(handle)?.version)
+    }
+
+    ///! Remove the asset, every handle to it goes stale
+    pub fn drop(&mut self, handle: Handle<T>) -> Option<Rc<T>> {
+        if self.slot(handle).is_err() {
+            return None
+        }
+        let slot = &mut self.slots[handle.index as usize];
+        slot.generation = slot.generation.wrapping_add(1);
+        self.paths.remove(&slot.path);
+        self.groups.remove(&slot.path);
+        if let Some(ref mut watched) = self.watched {
+            watched.remove(&slot.path);
+        }
+        self.free.push(handle.index);
+
+        let value = slot.value.take();
+        if value.is_some() {
+            let size = slot.size;
+            self.count(|s| s.resident_bytes -= size);
+        }
+        value
+    }
+
+    pub fn drop_path(&mut self, path: &str) -> Option<Rc<T>> {
+        match self.handle(path) {
+            Some(handle) => self.drop(handle),
+            None => None
+        }
+    }
+
+    pub fn clear(&mut self) {
+        let handles:Vec<Handle<T>> = self.paths.values().map(|&index| self.handle_at(index)).collect();
+        for handle in handles {
+            self.drop(handle);
+        }
+    }
+
+    ///! Turn hot reloading on or off, `reload_modified` does the actual checking
+    pub fn hot_reload(&mut self, enabled: bool) {
+        if !enabled {
+            self.watched = None;
+        }
+        else if self.watched.is_none() {
+            self.watched = Some(self.paths.keys().map(|k| (k.clone(), self.vfs.modified(k))).collect());
+        }
+    }
+
+    ///! Call `f` every time a hot reload swaps an asset
+    pub fn subscribe(&mut self, f: Box<Subscriber<T>>) {
+        self.subscribers.push(f);
+    }
+
+    ///! Run the loader again and swap the value behind every handle for path
+    pub fn reload(&mut self, path: &str) -> Result<Handle<T>, StorageError> {
+        let handle = match self.handle(path) {
+            Some(handle) => handle,
+            None => return self.load(path)
+        };
+
+        let data = self.run_loader(path)?;
+        self.replace(handle, data);
+        Ok(handle)
+    }
+
+    //swap in reloaded data and tell subscribers
+    fn replace(&mut self, handle: Handle<T>, data: T) {
+        self.fill(handle.index, data);
+        self.slots[handle.index as usize].version += 1;
+
+        let value = self.slots[handle.index as usize].value.clone().unwrap();
+        let path = &self.slots[handle.index as usize].path;
+        for f in &self.subscribers {
+            f(path, handle, &value);
+        }
+    }
+
+    //store data loaded outside of `load`, refilling the slot if path was evicted
+    fn put(&mut self, path: &str, data: T) -> Handle<T> {
+        match self.handle(path) {
+            Some(handle) => {
+                self.fill(handle.index, data);
+                handle
+            },
+            None => self.insert(path, data)
+        }
+    }
+
+    //paths whose file changed since they were loaded
+    fn changed(&self) -> Vec<String> {
+        match self.watched {
+            Some(ref watched) => watched.iter()
+                .filter(|&(path, time)| self.vfs.modified(path) != *time)
+                .map(|(path, _)| path.clone())
+                .collect(),
+            None => Vec::new()
+        }
+    }
+
+    //take the current modification time as seen so a failed reload isn't retried until the file changes again
+    fn rewatch(&mut self, path: &str) {
+        if let Some(ref mut watched) = self.watched {
+            watched.insert(path.to_string(), self.vfs.modified(path));
+        }
+    }
+
+    ///! Reload everything whose file changed since it was loaded, returns the reloaded paths.
+    ///! Failed reloads keep the old value and are reported on stderr.
+    pub fn reload_modified(&mut self) -> Vec<String> {
+        let mut ret = Vec::new();
+        for path in self.changed() {
+            match self.reload(&path) {
+                Ok(_) => ret.push(path),
+                Err(e) => {
+                    println_err!("Failed to reload {}: {}", path, e);
+                    self.rewatch(&path);
+                }
+            }
+        }
+        ret
+    }
+}
+
+impl<T> Storage<T> where T: Sized + Send + 'static {
+    ///! Start loading path on a worker thread, call `update`, `poll` or `wait` to collect it.
+    ///! Loads share rayon's pool with the `parallel` feature and a few threads of their own without it.
+    pub fn load_async(&mut self, path: &str) -> Pending<T> {
+        let pending = Pending {
+            path: path.to_string(),
+            _marker: PhantomData
+        };
+
+        if self.resident(path) || self.loading.contains(path) {
+            return pending
+        }
+
+        self.loading.insert(path.to_string());
+        self.failed.remove(path);
+        self.progress.requested += 1;
+
+        let loader = self.loader.clone();
+        let vfs = self.vfs.clone();
+        let sender = self.sender.clone();
+        let path = path.to_string();
+        spawn_load(Box::new(move || {
+            //a panicking loader fails its load instead of taking a worker down with it
+            let result = panic::catch_unwind(AssertUnwindSafe(|| loader(&mut LoadContext::new(&vfs, &path))))
+                .unwrap_or_else(|_| Err(From::from("loader panicked")))
+                .map_err(StorageError::from);
+            //the storage going away first just means nobody wants it anymore
+            let _ = sender.send((path, result));
+        }));
+        pending
+    }
+
+    fn finish(&mut self, (path, result): LoadResult<T>) {
+        self.loading.remove(&path);
+        match result {
+            Ok(data) => {
+                self.progress.loaded += 1;
+                match self.handle(&path) {
+                    Some(handle) => if !self.resident(&path) {
+                        self.fill(handle.index, data);
+                    },
+                    None => {
+                        self.insert(&path, data);
+                    }
+                }
+            },
+            Err(e) => {
+                self.progress.failed += 1;
+                if self.resident(&path) {
+                    return
+                }
+                if let Err(e) = self.fall_back(&path, e) {
+                    self.failed.insert(path, e);
+                }
+            }
+        }
+    }
+
+    ///! Move finished background loads into the store, returns their paths
+    pub fn update(&mut self) -> Vec<String> {
+        let mut ret = Vec::new();
+        while let Ok(result) = self.receiver.try_recv() {
+            ret.push(result.0.clone());
+            self.finish(result);
+        }
+        ret
+    }
+
+    ///! None while pending is still loading, done only once the value is resident
+    pub fn poll(&mut self, pending: &Pending<T>) -> Option<Result<Handle<T>, StorageError>> {
+        self.update();
+        //an evicted path keeps its handle while it loads again
+        if self.loading.contains(&pending.path) {
+            return None
+        }
+        if let Some(e) = self.failed.remove(&pending.path) {
+            return Some(Err(e))
+        }
+        match self.handle(&pending.path) {
+            Some(handle) if self.resident(&pending.path) => Some(Ok(handle)),
+            Some(_) => Some(Err(StorageError::Evicted(pending.path.clone()))),
+            None => Some(Err(StorageError::NotLoading(pending.path.clone())))
+        }
+    }
+
+    ///! Block until pending is done
+    pub fn wait(&mut self, pending: Pending<T>) -> Result<Handle<T>, StorageError> {
+        loop {
+            if let Some(ret) = self.poll(&pending) {
+                return ret
+            }
+            let result = self.receiver.recv().map_err(|_| StorageError::NotLoading(pending.path.clone()))?;
+            self.finish(result);
+        }
+    }
+
+    pub fn progress(&self) -> LoadProgress {
+        self.progress
+    }
+
+    ///! Start counting from zero again, loads still in flight stay counted
+    pub fn reset_progress(&mut self) {
+        self.progress = LoadProgress {
+            requested: self.loading.len(),
+            loaded: 0,
+            failed: 0
+        };
+    }
+}
+
+#[test]
+fn handles() {
+    let mut store = Storage::<usize>::new(Box::new(|ctx| Ok(ctx.path().len())));
+    let a = store.load("a").unwrap();
+    let abc = store.load("abc").unwrap();
+
+    assert_eq!(store.load("abc").unwrap(), abc);
+    assert_eq!(store.handle("abc"), Some(abc));
+    assert_eq!(*store.get(abc).unwrap(), 3);
+    assert_eq!(store.path(a).unwrap(), "a");
+
+    assert_eq!(store.drop(a).map(|x| *x), Some(1));
+    match store.get(a) {
+        Err(StorageError::StaleHandle) => {},
+        _ => panic!("dropped handle still resolves")
+    }
+
+    //slot gets reused, the old handle stays stale
+    let ab = store.load("ab").unwrap();
+    assert_ne!(ab, a);
+    assert!(store.get(a).is_err());
+    assert_eq!(*store.get(ab).unwrap(), 2);
+
+    match store.add("ab", 7) {
+        Err(StorageError::StorageOccupied(_)) => {},
+        _ => panic!("added over an existing path")
+    }
+
+    store.clear();
+    assert!(store.get(abc).is_err());
+    assert!(!store.has("abc"));
+}
+
+#[test]
+fn hot_reload() {
+    use std::cell::Cell;
+    use std::fs::File;
+    use std::io::Write;
+    use std::time::Duration;
+
+    let path = std::env::temp_dir().join("pipewrench_hot_reload.txt").to_string_lossy().into_owned();
+    let write = |text: &str, age: u64| {
+        let mut f = File::create(&path).unwrap();
+        f.write_all(text.as_bytes()).unwrap();
+        f.set_modified(SystemTime::now() - Duration::from_secs(age)).unwrap();
+    };
+    write("first", 60);
+
+    let mut store = Storage::<String>::new(Box::new(|ctx| Ok(ctx.read_string()?)));
+    let reloads = Rc::new(Cell::new(0));
+    let counter = reloads.clone();
+    store.subscribe(Box::new(move |_, _, _| counter.set(counter.get() + 1)));
+    store.hot_reload(true);
+
+    let asset = store.load(&path).unwrap();
+    assert_eq!(*store.get(asset).unwrap(), "first");
+    assert!(store.reload_modified().is_empty());
+
+    write("second", 0);
+    assert_eq!(store.reload_modified(), vec![path.clone()]);
+    assert_eq!(*store.get(asset).unwrap(), "second");
+    assert_eq!(store.version(asset).unwrap(), 1);
+    assert_eq!(reloads.get(), 1);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn async_load() {
+    let mut store = Storage::<usize>::new(Box::new(|ctx| {
+        if ctx.path().ends_with("missing") {
+            return Err(From::from("no such asset"))
+        }
+        if ctx.path() == "panics" {
+            panic!("loader panicked")
+        }
+        Ok(ctx.path().len())
+    }));
+
+    //more loads than there are workers still all finish
+    let many:Vec<_> = (1..20).map(|n| store.load_async(&"x".repeat(n))).collect();
+    for (n, pending) in (1..20).zip(many) {
+        let handle = store.wait(pending).unwrap();
+        assert_eq!(*store.get(handle).unwrap(), n);
+    }
+    let panics = store.load_async("panics");
+    assert_eq!(store.wait(panics).unwrap_err().to_string(), "Error: loader panicked");
+    store.reset_progress();
+
+    let a = store.load_async("a/b");
+    let b = store.load_async("abcd");
+    let missing = store.load_async("missing");
+    //already in flight, not requested twice
+    store.load_async("abcd");
+    assert_eq!(store.progress().requested, 3);
+
+    let a = store.wait(a).unwrap();
+    assert_eq!(*store.get(a).unwrap(), 3);
+    let b = store.wait(b).unwrap();
+    assert_eq!(*store.get(b).unwrap(), 4);
+    assert_eq!(store.wait(missing).unwrap_err().to_string(), "Error: no such asset");
+
+    assert_eq!(store.progress(), LoadProgress { requested: 3, loaded: 2, failed: 1 });
+    assert!(store.progress().done());
+    assert!(store.has("abcd"));
+
+    let never = Pending::<usize> { path: "never".to_string(), _marker: PhantomData };
+    match store.poll(&never) {
+        Some(Err(StorageError::NotLoading(_))) => {},
+        _ => panic!("never was never requested")
+    }
+}
+
+#[test]
+fn eviction() {
+    let mut store = Storage::<String>::new(Box::new(|ctx| {
+        let mut s = String::with_capacity(4);
+        s.push_str(ctx.path());
+        Ok(s)
+    })).with_eviction(Eviction::Lru(10)).with_asset_size();
+
+    let a = store.load("aaaa").unwrap();
+    let b = store.load("bbbb").unwrap();
+    assert_eq!(store.stats().resident_bytes, 8);
+
+    //a is the most recent now, b goes when c pushes us over
+    store.get(a).unwrap();
+    let c = store.load("cccc").unwrap();
+    assert_eq!(store.stats().resident_bytes, 8);
+    match store.get(b) {
+        Err(StorageError::Evicted(ref p)) if p == "bbbb" => {},
+        _ => panic!("b should have been evicted")
+    }
+
+    //held assets are never evicted, a goes instead of c
+    let held = store.get(c).unwrap();
+    assert_eq!(store.load("bbbb").unwrap(), b);
+    assert!(store.get(a).is_err());
+    assert_eq!(*held, "cccc");
+
+    assert_eq!(store.stats(), StorageStats { hits: 2, misses: 6, evictions: 2, resident_bytes: 8 });
+
+    let mut unused = Storage::<usize>::new(Box::new(|ctx| Ok(ctx.path().len()))).with_eviction(Eviction::Unused);
+    let x = unused.load("x").unwrap();
+    let y = unused.load("yy").unwrap();
+    let keep = unused.get(y).unwrap();
+    assert_eq!(unused.evict(), 1);
+    assert!(unused.get(x).is_err());
+    assert_eq!(*keep, 2);
+
+    //loading an evicted path again waits for the new value instead of handing back the empty slot
+    let pending = unused.load_async("x");
+    let again = unused.wait(pending).unwrap();
+    assert_eq!(again, x);
+    assert_eq!(*unused.get(again).unwrap(), 1);
+}
+
+#[test]
+fn vfs_load() {
+    let vfs = Vfs::new()
+        .with_mount(Memory::new().with_file("text/a.txt", &b"base"[..]))
+        .with_mount(Memory::new().with_file("text/a.txt", &b"override"[..]));
+    let mut store = Storage::<String>::new(Box::new(|ctx| {
+        assert_eq!(ctx.extension(), Some("txt"));
+        Ok(ctx.read_string()?)
+    })).with_vfs(Arc::new(vfs));
+
+    let a = store.load("text/a.txt").unwrap();
+    assert_eq!(*store.get(a).unwrap(), "override");
+    assert!(store.load("text/missing.txt").is_err());
+}
+
+#[test]
+fn fallback() {
+    let vfs = Vfs::new().with_mount(Memory::new().with_file("a.txt", &b"text"[..]));
+    let mut store = Storage::<String>::new(Box::new(|ctx| Ok(ctx.read_string()?)))
+        .with_vfs(Arc::new(vfs))
+        .with_fallback("missing".to_string());
+
+    let a = store.load("a.txt").unwrap();
+    let b = store.load("b.txt").unwrap();
+    assert_eq!(*store.get(b).unwrap(), "missing");
+    assert!(store.is_fallback(b).unwrap());
+    assert!(!store.is_fallback(a).unwrap());
+    //cached like anything else, the loader isn't tried again
+    assert_eq!(store.load("b.txt").unwrap(), b);
+    assert_eq!(store.errors().len(), 1);
+    assert_eq!(store.errors()[0].path, "b.txt");
+
+    let pending = store.load_async("c.txt");
+    let c = store.wait(pending).unwrap();
+    assert!(store.is_fallback(c).unwrap());
+    assert_eq!(store.progress().failed, 1);
+    assert_eq!(store.errors().len(), 2);
+
+    store.clear_errors();
+    assert!(store.errors().is_empty());
+    store.set_fallback(None);
+    assert!(store.load("d.txt").is_err());
+    assert!(store.errors().is_empty());
+}
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use super::embed::asset_files;
+use super::vfs::{Mount, Reader, VfsError};
+
+const MAGIC: &[u8; 4] = b"PWPK";
+const VERSION: u32 = 1;
+
+///! A single file archive of assets mounted read only.
+///! Layout is the magic, a version, an index of (name, offset, length) and then the data, little endian throughout.
+#[derive(Debug,Clone)]
+pub struct Pack {
+    path: PathBuf,
+    entries: HashMap<String, (u64, u64)>,
+    modified: Option<SystemTime>
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+//header is the magic, version and entry count, each entry is at least a name length, offset and length
+const HEADER_LEN: u64 = 12;
+const MIN_ENTRY_LEN: u64 = 20;
+
+fn corrupt(msg: &str) -> VfsError {
+    VfsError::Corrupt(msg.to_string())
+}
+
+impl Pack {
+    ///! Read the index, file contents are only read when opened
+    ///! Sizes in the index are checked against the file so a corrupt pack can't ask for huge allocations
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Pack, VfsError> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::open(&path)?;
+        let metadata = file.metadata()?;
+        let file_len = metadata.len();
+        let mut f = io::BufReader::new(file);
+
+        if file_len < HEADER_LEN {
+            return Err(VfsError::NotAPack)
+        }
+        let mut magic = [0; 4];
+        f.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(VfsError::NotAPack)
+        }
+        let version = read_u32(&mut f)?;
+        if version != VERSION {
+            return Err(VfsError::UnsupportedVersion(version))
+        }
+
+        let count = read_u32(&mut f)? as u64;
+        let mut remaining = file_len - HEADER_LEN;
+        if count > remaining / MIN_ENTRY_LEN {
+            return Err(corrupt("entry count is larger than the file"))
+        }
+        let mut entries = HashMap::with_capacity(count as usize);
+        for _ in 0..count {
+            let name_len = read_u32(&mut f)? as u64;
+            if name_len + MIN_ENTRY_LEN > remaining {
+                return Err(corrupt("entry name is longer than the file"))
+            }
+            remaining -= MIN_ENTRY_LEN + name_len;
+
+            let mut name = vec![0; name_len as usize];
+            f.read_exact(&mut name)?;
+            let name = String::from_utf8(name).map_err(|_| corrupt("entry name isn't utf-8"))?;
+            let offset = read_u64(&mut f)?;
+            let len = read_u64(&mut f)?;
+            if offset.checked_add(len).map(|end| end > file_len).unwrap_or(true) {
+                return Err(corrupt(&format!("\"{}\" runs past the end of the file", name)))
+            }
+            entries.insert(name, (offset, len));
+        }
+
+        let modified = metadata.modified().ok();
+        Ok(Pack { path, entries, modified })
+    }
+
+    ///! Write files into a new pack
+    pub fn write<W: Write>(out: &mut W, files: &[(&str, &[u8])]) -> io::Result<()> {
+        let index_len:usize = files.iter().map(|&(name, _)| 4 + name.len() + 16).sum();
+        let mut offset = (MAGIC.len() + 8 + index_len) as u64;
+
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&(files.len() as u32).to_le_bytes())?;
+        for &(name, data) in files {
+            out.write_all(&(name.len() as u32).to_le_bytes())?;
+            out.write_all(name.as_bytes())?;
+            out.write_all(&offset.to_le_bytes())?;
+            out.write_all(&(data.len() as u64).to_le_bytes())?;
+            offset += data.len() as u64;
+        }
+        for &(_, data) in files {
+            out.write_all(data)?;
+        }
+        Ok(())
+    }
+
+    ///! Pack everything under dir into out, names are relative to dir with / separators
+    pub fn build<P: AsRef<Path>, Q: AsRef<Path>>(dir: P, out: Q) -> io::Result<()> {
+        let mut files = Vec::new();
+        for name in asset_files(&dir)? {
+            let data = fs::read(dir.as_ref().join(&name))?;
+            files.push((name, data));
+        }
+
+        let borrowed:Vec<(&str, &[u8])> = files.iter().map(|(n, d)| (&n[..], &d[..])).collect();
+        let mut f = io::BufWriter::new(File::create(out)?);
+        Pack::write(&mut f, &borrowed)?;
+        f.flush()
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.entries.keys().map(|k| &k[..]).collect()
+    }
+}
+
+impl Mount for Pack {
+    fn open(&self, path: &str) -> Option<io::Result<Reader>> {
+        let (offset, len) = *self.entries.get(path)?;
+        Some(File::open(&self.path).and_then(|mut f| {
+            f.seek(SeekFrom::Start(offset))?;
+            Ok(Box::new(f.take(len)) as Reader)
+        }))
+    }
+
+    fn contains(&self, path: &str) -> bool {
+        self.entries.contains_key(path)
+    }
+
+    fn modified(&self, path: &str) -> Option<SystemTime> {
+        if self.contains(path) { self.modified } else { None }
+    }
+}
+
+#[test]
+fn pack_round_trip() {
+    use super::vfs::{Memory, Vfs};
+
+    let path = std::env::temp_dir().join("pipewrench_pack_round_trip.pak");
+    {
+        let mut f = File::create(&path).unwrap();
+        Pack::write(&mut f, &[("a.txt", b"from the pack"), ("dir/b.bin", &[1, 2, 3])]).unwrap();
+    }
+
+    let pack = Pack::open(&path).unwrap();
+    assert!(pack.contains("dir/b.bin"));
+    assert!(!pack.contains("c.txt"));
+
+    //the pack overrides the base mount but falls through for what it doesn't have
+    let vfs = Vfs::new()
+        .with_mount(Memory::new().with_file("a.txt", &b"base"[..]).with_file("c.txt", &b"base"[..]))
+        .with_mount(pack);
+    assert_eq!(vfs.read("a.txt").unwrap(), b"from the pack");
+    assert_eq!(vfs.read("dir/b.bin").unwrap(), vec![1, 2, 3]);
+    assert_eq!(vfs.read("c.txt").unwrap(), b"base");
+
+    fs::remove_file(&path).unwrap();
+    match Pack::open(&path) {
+        Err(VfsError::IoError { ref source }) if source.kind() == io::ErrorKind::NotFound => {},
+        other => panic!("{:?}", other.map(|_| ()))
+    }
+}
+
+#[test]
+fn corrupt_pack() {
+    let path = std::env::temp_dir().join("pipewrench_corrupt_pack.pak");
+    let header = |count: u32| {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&count.to_le_bytes());
+        bytes
+    };
+
+    //an index claiming billions of entries
+    fs::write(&path, header(u32::MAX)).unwrap();
+    assert!(matches!(Pack::open(&path), Err(VfsError::Corrupt(_))));
+
+    //one entry whose name is far longer than the file
+    let mut bytes = header(1);
+    bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+    bytes.extend_from_slice(&[0; 16]);
+    fs::write(&path, bytes).unwrap();
+    assert!(matches!(Pack::open(&path), Err(VfsError::Corrupt(_))));
+
+    //the first entry's name eats the room the second entry needs
+    let mut bytes = header(2);
+    bytes.extend_from_slice(&21u32.to_le_bytes());
+    bytes.extend_from_slice(&[b'a'; 21]);
+    bytes.extend_from_slice(&[0; 16]);
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+    fs::write(&path, bytes).unwrap();
+    assert!(matches!(Pack::open(&path), Err(VfsError::Corrupt(_))));
+
+    //data past the end of the file
+    let mut bytes = Vec::new();
+    Pack::write(&mut bytes, &[("a.txt", b"abc")]).unwrap();
+    bytes.pop();
+    fs::write(&path, bytes).unwrap();
+    assert!(matches!(Pack::open(&path), Err(VfsError::Corrupt(_))));
+
+    fs::write(&path, b"PWPK").unwrap();
+    assert!(matches!(Pack::open(&path), Err(VfsError::NotAPack)));
+    fs::remove_file(&path).unwrap();
+}
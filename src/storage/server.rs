@@ -0,0 +1,457 @@
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::{Arc, RwLock};
+use super::{AssetKey, BoxedError, Handle, LoadContext, LoaderFunction, Storage, StorageError, Vfs};
+
+type Loaders<T> = Arc<RwLock<HashMap<String, Arc<LoaderFunction<T>>>>>;
+type Reloader = fn(&mut AssetServer, &str) -> Result<(), StorageError>;
+
+//what the server needs from a Storage without knowing its type
+trait AnyStore {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn hot_reload(&mut self, enabled: bool);
+    fn changed(&self) -> Vec<String>;
+    fn rewatch(&mut self, path: &str);
+    fn drop_path(&mut self, path: &str);
+    fn clear(&mut self);
+    fn reloader(&self) -> Reloader;
+}
+
+struct TypedStore<T> {
+    storage: Storage<T>,
+    loaders: Loaders<T>
+}
+
+impl<T: 'static> AnyStore for TypedStore<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn hot_reload(&mut self, enabled: bool) {
+        self.storage.hot_reload(enabled);
+    }
+
+    fn changed(&self) -> Vec<String> {
+        self.storage.changed()
+    }
+
+    fn rewatch(&mut self, path: &str) {
+        self.storage.rewatch(path);
+    }
+
+    fn drop_path(&mut self, path: &str) {
+        self.storage.drop_path(path);
+    }
+
+    fn clear(&mut self) {
+        self.storage.clear();
+    }
+
+    fn reloader(&self) -> Reloader {
+        |server, path| server.reload_one::<T>(path).map(|_| ())
+    }
+}
+
+//loaders box up StorageErrors from dependencies, don't wrap them twice
+fn unbox(e: BoxedError) -> StorageError {
+    match e.downcast::<StorageError>() {
+        Ok(e) => *e,
+        Err(e) => StorageError::from(e)
+    }
+}
+
+fn extension_of(path: &str) -> String {
+    Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase()
+}
+
+///! One Storage per asset type, with loaders picked by file extension.
+///! `load::<Texture>("x.png")` goes to whichever loader was registered for Texture and png.
+///! Loaders can load other assets through `LoadContext::load`, reloading or dropping those cascades.
+pub struct AssetServer {
+    vfs: Arc<Vfs>,
+    stores: HashMap<TypeId, Box<dyn AnyStore>>,
+    dependencies: HashMap<AssetKey, Vec<AssetKey>>,
+    dependents: HashMap<AssetKey, HashSet<AssetKey>>,
+    //assets being loaded right now, outermost first
+    stack: Vec<AssetKey>
+}
+
+impl AssetServer {
+    pub fn new() -> AssetServer {
+        AssetServer {
+            vfs: Arc::new(Vfs::native()),
+            stores: HashMap::new(),
+            dependencies: HashMap::new(),
+            dependents: HashMap::new(),
+            stack: Vec::new()
+        }
+    }
+
+    ///! Every Storage registered after this reads through vfs
+    pub fn with_vfs(mut self, vfs: Arc<Vfs>) -> Self {
+        self.vfs = vfs;
+        self
+    }
+
+    fn typed<T: 'static>(&self) -> Option<&TypedStore<T>> {
+        self.stores.get(&TypeId::of::<T>()).and_then(|s| s.as_any().downcast_ref())
+    }
+
+    fn typed_mut<T: 'static>(&mut self) -> Option<&mut TypedStore<T>> {
+        self.stores.get_mut(&TypeId::of::<T>()).and_then(|s| s.as_any_mut().downcast_mut())
+    }
+
+    ///! Use loader for T assets with any of extensions, replacing earlier registrations
+    pub fn register<T, F>(&mut self, extensions: &[&str], loader: F) where T: 'static, F: Fn(&mut LoadContext) -> Result<T, BoxedError> + Send + Sync + 'static {
+        if self.typed::<T>().is_none() {
+            let loaders:Loaders<T> = Arc::new(RwLock::new(HashMap::new()));
+            let table = loaders.clone();
+            let storage = Storage::new(Box::new(move |ctx: &mut LoadContext| {
+                let ext = extension_of(ctx.path());
+                let loader = table.read().unwrap().get(&ext).cloned();
+                match loader {
+                    Some(loader) => loader(ctx),
+                    None => Err(Box::new(StorageError::NoLoader(ctx.path().to_string())))
+                }
+            })).with_vfs(self.vfs.clone());
+            self.stores.insert(TypeId::of::<T>(), Box::new(TypedStore { storage, loaders }));
+        }
+
+        let loader:Arc<LoaderFunction<T>> = Arc::new(loader);
+        let store = self.typed_mut::<T>().unwrap();
+        let mut table = store.loaders.write().unwrap();
+        for ext in extensions {
+            table.insert(ext.trim_start_matches('.').to_lowercase(), loader.clone());
+        }
+    }
+
+    ///! Is there a T loader for path's extension
+    pub fn can_load<T: 'static>(&self, path: &str) -> bool {
+        match self.typed::<T>() {
+            Some(store) => store.loaders.read().unwrap().contains_key(&extension_of(path)),
+            None => false
+        }
+    }
+
+    //run the T loader for path, recording what it depends on
+    fn run<T: 'static>(&mut self, path: &str) -> Result<T, StorageError> {
+        let key = AssetKey::of::<T>(path);
+        if let Some(start) = self.stack.iter().position(|k| *k == key) {
+            let mut chain:Vec<&str> = self.stack[start..].iter().map(|k| &k.path[..]).collect();
+            chain.push(path);
+            return Err(StorageError::DependencyCycle(chain.join(" -> ")))
+        }
+
+        let (loader, vfs) = match self.typed::<T>() {
+            Some(store) => (store.storage.loader.clone(), store.storage.vfs.clone()),
+            None => return Err(StorageError::NoLoader(path.to_string()))
+        };
+
+        self.stack.push(key.clone());
+        let (result, dependencies) = {
+            let mut ctx = LoadContext::new(&vfs, path).with_server(self);
+            let result = loader(&mut ctx);
+            (result, ctx.into_dependencies())
+        };
+        self.stack.pop();
+
+        let data = result.map_err(unbox)?;
+        self.link(key, dependencies);
+        Ok(data)
+    }
+
+    fn link(&mut self, key: AssetKey, dependencies: Vec<AssetKey>) {
+        self.unlink(&key);
+        for dep in &dependencies {
+            self.dependents.entry(dep.clone()).or_default().insert(key.clone());
+        }
+        if !dependencies.is_empty() {
+            self.dependencies.insert(key, dependencies);
+        }
+    }
+
+    //forget what key depends on
+    fn unlink(&mut self, key: &AssetKey) {
+        for dep in self.dependencies.remove(key).unwrap_or_default() {
+            let empty = match self.dependents.get_mut(&dep) {
+                Some(users) => {
+                    users.remove(key);
+                    users.is_empty()
+                },
+                None => false
+            };
+            if empty {
+                self.dependents.remove(&dep);
+            }
+        }
+    }
+
+    ///! Everything that depends on key directly or indirectly, dependencies before their dependents
+    pub fn dependents_of(&self, key: &AssetKey) -> Vec<AssetKey> {
+        fn visit(server: &AssetServer, key: &AssetKey, seen: &mut HashSet<AssetKey>, order: &mut Vec<AssetKey>) {
+            if let Some(users) = server.dependents.get(key) {
+                for user in users {
+                    if seen.insert(user.clone()) {
+                        visit(server, user, seen, order);
+                        order.push(user.clone());
+                    }
+                }
+            }
+        }
+
+        let mut seen = HashSet::new();
+        let mut order = Vec::new();
+        visit(self, key, &mut seen, &mut order);
+        order.reverse();
+        order
+    }
+
+    ///! What key's loader loaded through its context
+    pub fn dependencies_of(&self, key: &AssetKey) -> &[AssetKey] {
+        self.dependencies.get(key).map(|d| &d[..]).unwrap_or(&[])
+    }
+
+    pub fn load<T: 'static>(&mut self, path: &str) -> Result<Handle<T>, StorageError> {
+        if !self.can_load::<T>(path) {
+            return Err(StorageError::NoLoader(path.to_string()))
+        }
+        {
+            let storage = &mut self.typed_mut::<T>().unwrap().storage;
+            if storage.resident(path) {
+                return storage.load(path)
+            }
+        }
+
+        let result = self.run::<T>(path);
+        let storage = &mut self.typed_mut::<T>().unwrap().storage;
+        storage.count(|s| s.misses += 1);
+        match result {
+            Ok(data) => Ok(storage.put(path, data)),
+            Err(e) => storage.fall_back(path, e)
+        }
+    }
+
+    //reload path without touching its dependents
+    fn reload_one<T: 'static>(&mut self, path: &str) -> Result<Handle<T>, StorageError> {
+        let handle = match self.storage::<T>().and_then(|s| s.handle(path)) {
+            Some(handle) => handle,
+            None => return self.load(path)
+        };
+        let data = self.run::<T>(path)?;
+        self.typed_mut::<T>().unwrap().storage.replace(handle, data);
+        Ok(handle)
+    }
+
+    //reload key and then everything using it, failed dependents keep their old value
+    fn reload_key(&mut self, key: &AssetKey) -> Result<(), StorageError> {
+        let reload = match self.stores.get(&key.type_id) {
+            Some(store) => store.reloader(),
+            None => return Err(StorageError::NoLoader(key.path.clone()))
+        };
+        reload(self, &key.path)?;
+
+        for user in self.dependents_of(key) {
+            let reload = self.stores[&user.type_id].reloader();
+            if let Err(e) = reload(self, &user.path) {
+                println_err!("Failed to reload {} after {} changed: {}", user.path, key.path, e);
+            }
+        }
+        Ok(())
+    }
+
+    ///! Reload path and every asset that depends on it
+    pub fn reload<T: 'static>(&mut self, path: &str) -> Result<Handle<T>, StorageError> {
+        self.reload_key(&AssetKey::of::<T>(path))?;
+        self.storage::<T>().and_then(|s| s.handle(path)).ok_or_else(|| StorageError::NoLoader(path.to_string()))
+    }
+
+    ///! Drop the asset and everything depending on it
+    pub fn drop<T: 'static>(&mut self, handle: Handle<T>) -> Option<Rc<T>> {
+        let key = match self.storage::<T>().map(|s| s.path(handle)) {
+            Some(Ok(path)) => AssetKey::of::<T>(path),
+            _ => return None
+        };
+
+        for user in self.dependents_of(&key) {
+            if let Some(store) = self.stores.get_mut(&user.type_id) {
+                store.drop_path(&user.path);
+            }
+            self.unlink(&user);
+        }
+        self.unlink(&key);
+        self.dependents.remove(&key);
+        self.typed_mut::<T>().unwrap().storage.drop(handle)
+    }
+
+    pub fn get<T: 'static>(&self, handle: Handle<T>) -> Result<Rc<T>, StorageError> {
+        match self.typed::<T>() {
+            Some(store) => store.storage.get(handle),
+            None => Err(StorageError::StaleHandle)
+        }
+    }
+
+    ///! The Storage behind T, None until a T loader is registered
+    pub fn storage<T: 'static>(&self) -> Option<&Storage<T>> {
+        self.typed::<T>().map(|s| &s.storage)
+    }
+
+    pub fn storage_mut<T: 'static>(&mut self) -> Option<&mut Storage<T>> {
+        self.typed_mut::<T>().map(|s| &mut s.storage)
+    }
+
+    pub fn hot_reload(&mut self, enabled: bool) {
+        for store in self.stores.values_mut() {
+            store.hot_reload(enabled);
+        }
+    }
+
+    ///! Reload every changed asset along with its dependents, returns the changed paths.
+    ///! Failed reloads keep the old value and are reported on stderr.
+    pub fn reload_modified(&mut self) -> Vec<String> {
+        let changed:Vec<AssetKey> = self.stores.iter()
+            .flat_map(|(&type_id, store)| store.changed().into_iter().map(move |path| AssetKey { type_id, path }))
+            .collect();
+
+        let mut ret = Vec::new();
+        for key in changed {
+            match self.reload_key(&key) {
+                Ok(_) => ret.push(key.path),
+                Err(e) => {
+                    println_err!("Failed to reload {}: {}", key.path, e);
+                    if let Some(store) = self.stores.get_mut(&key.type_id) {
+                        store.rewatch(&key.path);
+                    }
+                }
+            }
+        }
+        ret
+    }
+
+    pub fn clear(&mut self) {
+        for store in self.stores.values_mut() {
+            store.clear();
+        }
+        self.dependencies.clear();
+        self.dependents.clear();
+    }
+}
+
+impl Default for AssetServer {
+    fn default() -> AssetServer {
+        AssetServer::new()
+    }
+}
+
+#[test]
+fn asset_server() {
+    use super::Memory;
+
+    #[derive(Debug, PartialEq)]
+    struct Image(&'static str, usize);
+    #[derive(Debug, PartialEq)]
+    struct Text(String);
+
+    let vfs = Memory::new()
+        .with_file("a.png", &b"png!"[..])
+        .with_file("b.BMP", &b"bmp"[..])
+        .with_file("c.txt", &b"hello"[..]);
+    let mut server = AssetServer::new().with_vfs(Arc::new(Vfs::new().with_mount(vfs)));
+
+    server.register(&["png"], |ctx| Ok(Image("png", ctx.read_bytes()?.len())));
+    server.register(&["bmp", ".dib"], |ctx| Ok(Image("bmp", ctx.read_bytes()?.len())));
+    server.register(&["txt"], |ctx| Ok(Text(ctx.read_string()?)));
+
+    let a = server.load::<Image>("a.png").unwrap();
+    let b = server.load::<Image>("b.BMP").unwrap();
+    let c = server.load::<Text>("c.txt").unwrap();
+    assert_eq!(*server.get(a).unwrap(), Image("png", 4));
+    assert_eq!(*server.get(b).unwrap(), Image("bmp", 3));
+    assert_eq!(*server.get(c).unwrap(), Text("hello".to_string()));
+    assert_eq!(server.load::<Image>("a.png").unwrap(), a);
+
+    assert!(server.can_load::<Image>("x.dib"));
+    match server.load::<Text>("a.png") {
+        Err(StorageError::NoLoader(ref p)) if p == "a.png" => {},
+        _ => panic!("Text has no png loader")
+    }
+    match server.load::<u32>("c.txt") {
+        Err(StorageError::NoLoader(_)) => {},
+        _ => panic!("nothing loads u32")
+    }
+    assert_eq!(server.storage::<Image>().map(|s| s.has("b.BMP")), Some(true));
+}
+
+#[test]
+fn asset_dependencies() {
+    use std::cell::Cell;
+    use super::Memory;
+
+    struct Texture(String);
+    struct Material(Handle<Texture>, Handle<Texture>);
+    struct Level(Handle<Material>);
+
+    let vfs = Memory::new()
+        .with_file("a.png", &b"a"[..])
+        .with_file("b.png", &b"b"[..])
+        .with_file("m.mat", &b"a.png b.png"[..])
+        .with_file("l.lvl", &b"m.mat"[..])
+        .with_file("loop.lvl", &b"loop.mat"[..])
+        .with_file("loop.mat", &b"loop.lvl"[..]);
+    let mut server = AssetServer::new().with_vfs(Arc::new(Vfs::new().with_mount(vfs)));
+
+    server.register(&["png"], |ctx| Ok(Texture(ctx.read_string()?)));
+    server.register(&["mat"], |ctx| {
+        let text = ctx.read_string()?;
+        let names:Vec<&str> = text.split(' ').collect();
+        if names[0].ends_with(".lvl") {
+            ctx.load::<Level>(names[0])?;
+        }
+        Ok(Material(ctx.load(names[0])?, ctx.load(names[1])?))
+    });
+    server.register(&["lvl"], |ctx| {
+        let text = ctx.read_string()?;
+        Ok(Level(ctx.load(&text)?))
+    });
+
+    let level = server.load::<Level>("l.lvl").unwrap();
+    let material = server.get(server.get(level).unwrap().0).unwrap();
+    assert_eq!(server.get(material.0).unwrap().0, "a");
+    assert_eq!(server.get(material.1).unwrap().0, "b");
+    let a = AssetKey::of::<Texture>("a.png");
+    let mat = AssetKey::of::<Material>("m.mat");
+    assert_eq!(server.dependencies_of(&mat).len(), 2);
+    assert_eq!(server.dependents_of(&a), vec![mat.clone(), AssetKey::of::<Level>("l.lvl")]);
+
+    let reloads = Rc::new(Cell::new(0));
+    let counter = reloads.clone();
+    server.storage_mut::<Level>().unwrap().subscribe(Box::new(move |_, _, _| counter.set(counter.get() + 1)));
+    server.reload::<Texture>("a.png").unwrap();
+    assert_eq!(reloads.get(), 1);
+    let version = |s: &Storage<Texture>, path| s.version(s.handle(path).unwrap()).unwrap();
+    assert_eq!(version(server.storage().unwrap(), "a.png"), 1);
+    assert_eq!(version(server.storage().unwrap(), "b.png"), 0);
+    let materials = server.storage::<Material>().unwrap();
+    assert_eq!(materials.version(materials.handle("m.mat").unwrap()).unwrap(), 1);
+
+    //dropping a texture takes the material and level with it
+    let tex = server.storage::<Texture>().unwrap().handle("a.png").unwrap();
+    assert_eq!(server.drop(tex).map(|t| t.0.clone()), Some("a".to_string()));
+    assert!(server.get(level).is_err());
+    assert!(!server.storage::<Material>().unwrap().has("m.mat"));
+    assert!(server.storage::<Texture>().unwrap().has("b.png"));
+    assert!(server.dependents_of(&AssetKey::of::<Texture>("b.png")).is_empty());
+
+    match server.load::<Level>("loop.lvl") {
+        Err(StorageError::DependencyCycle(ref chain)) => assert_eq!(chain, "loop.lvl -> loop.mat -> loop.lvl"),
+        Err(e) => panic!("expected a cycle, got {}", e),
+        Ok(_) => panic!("expected a cycle")
+    }
+    assert!(!server.storage::<Level>().unwrap().has("loop.lvl"));
+}
@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock};
+use super::{LoadContext, LoaderFunction, StorageError, Vfs};
+
+//locked on its own so loading one path doesn't block the rest
+type Entry<T> = Arc<Mutex<Option<Arc<T>>>>;
+
+//a loader that panicked only poisons its own entry, the value in it is still None or the last good one
+fn lock<T>(entry: &Entry<T>) -> MutexGuard<'_, Option<Arc<T>>> {
+    entry.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+///! Storage that can be shared between threads, assets are handed out as `Arc`s.
+///! Threads loading the same path at the same time wait for the first one instead of loading it twice.
+pub struct SyncStorage<T> {
+    entries: RwLock<HashMap<String, Entry<T>>>,
+    loader: Arc<LoaderFunction<T>>,
+    vfs: Arc<Vfs>
+}
+
+impl<T> SyncStorage<T> where T: Send + Sync {
+    pub fn new(loader: Box<LoaderFunction<T>>) -> SyncStorage<T> {
+        SyncStorage {
+            entries: RwLock::new(HashMap::new()),
+            loader: Arc::from(loader),
+            vfs: Arc::new(Vfs::native())
+        }
+    }
+
+    pub fn with_vfs(mut self, vfs: Arc<Vfs>) -> Self {
+        self.vfs = vfs;
+        self
+    }
+
+    fn entry(&self, path: &str) -> Entry<T> {
+        if let Some(entry) = self.entries.read().unwrap().get(path) {
+            return entry.clone()
+        }
+        self.entries.write().unwrap().entry(path.to_string()).or_default().clone()
+    }
+
+    fn run_loader(&self, path: &str) -> Result<Arc<T>, StorageError> {
+        Ok(Arc::new((self.loader)(&mut LoadContext::new(&self.vfs, path))?))
+    }
+
+    //after a failed load, drop entry unless someone else is using it or it has a value
+    fn forget(&self, path: &str, entry: &Entry<T>) {
+        let mut entries = self.entries.write().unwrap();
+        let empty = match entry.try_lock() {
+            Ok(value) => value.is_none(),
+            Err(_) => false
+        };
+        if empty && entries.get(path).map(|e| Arc::ptr_eq(e, entry)).unwrap_or(false) {
+            entries.remove(path);
+        }
+    }
+
+    ///! consume data and return a pointer to it
+    pub fn add(&self, path: &str, data: T) -> Result<Arc<T>, StorageError> {
+        let entry = self.entry(path);
+        let mut value = lock(&entry);
+        if value.is_some() {
+            return Err(StorageError::StorageOccupied(path.to_string()))
+        }
+        let data = Arc::new(data);
+        *value = Some(data.clone());
+        Ok(data)
+    }
+
+    pub fn load(&self, path: &str) -> Result<Arc<T>, StorageError> {
+        let entry = self.entry(path);
+        let mut value = lock(&entry);
+        if let Some(ref data) = *value {
+            return Ok(data.clone())
+        }
+        //anyone else after path waits on the lock until this is done
+        match self.run_loader(path) {
+            Ok(data) => {
+                *value = Some(data.clone());
+                Ok(data)
+            },
+            Err(e) => {
+                drop(value);
+                self.forget(path, &entry);
+                Err(e)
+            }
+        }
+    }
+
+    ///! None when path isn't loaded, waits if it's loading right now
+    pub fn get(&self, path: &str) -> Option<Arc<T>> {
+        let entry = self.entries.read().unwrap().get(path)?.clone();
+        let value = lock(&entry);
+        value.clone()
+    }
+
+    pub fn has(&self, path: &str) -> bool {
+        self.get(path).is_some()
+    }
+
+    ///! Run the loader again, assets already handed out keep the old value
+    pub fn reload(&self, path: &str) -> Result<Arc<T>, StorageError> {
+        let entry = self.entry(path);
+        let mut value = lock(&entry);
+        match self.run_loader(path) {
+            Ok(data) => {
+                *value = Some(data.clone());
+                Ok(data)
+            },
+            Err(e) => {
+                drop(value);
+                self.forget(path, &entry);
+                Err(e)
+            }
+        }
+    }
+
+    pub fn drop_path(&self, path: &str) -> Option<Arc<T>> {
+        let entry = self.entries.write().unwrap().remove(path)?;
+        let mut value = lock(&entry);
+        value.take()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap().values().filter(|e| lock(e).is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.entries.write().unwrap().clear();
+    }
+}
+
+#[test]
+fn sync_storage() {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    fn send_sync<T: Send + Sync>() {}
+    send_sync::<SyncStorage<String>>();
+    send_sync::<StorageError>();
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let panicked = AtomicBool::new(false);
+    let store = Arc::new(SyncStorage::<String>::new(Box::new(move |ctx| {
+        if ctx.path() == "missing" {
+            return Err(From::from("no such asset"))
+        }
+        if ctx.path() == "flaky" && !panicked.swap(true, Ordering::SeqCst) {
+            panic!("loader panicked")
+        }
+        counter.fetch_add(1, Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(20));
+        Ok(ctx.path().to_uppercase())
+    })));
+
+    let threads:Vec<_> = (0..8).map(|_| {
+        let store = store.clone();
+        thread::spawn(move || store.load("shared").unwrap())
+    }).collect();
+    for t in threads {
+        assert_eq!(*t.join().unwrap(), "SHARED");
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    let err = thread::spawn({
+        let store = store.clone();
+        move || store.load("missing").unwrap_err()
+    }).join().unwrap();
+    assert_eq!(err.to_string(), "Error: no such asset");
+    assert!(!store.has("missing"));
+    assert!(!store.entries.read().unwrap().contains_key("missing"));
+
+    //the panic poisons the entry but the next load of it still works
+    let flaky = store.clone();
+    assert!(thread::spawn(move || flaky.load("flaky")).join().is_err());
+    assert!(!store.has("flaky"));
+    assert_eq!(*store.load("flaky").unwrap(), "FLAKY");
+
+    assert!(store.add("shared", "again".to_string()).is_err());
+    assert_eq!(store.len(), 2);
+    store.drop_path("flaky");
+    assert_eq!(store.drop_path("shared").map(|s| s.len()), Some(6));
+    assert!(store.is_empty());
+}
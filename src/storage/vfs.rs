@@ -0,0 +1,201 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Cursor, Read};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+pub type Reader = Box<dyn Read + Send>;
+
+#[derive(Debug, Error)]
+pub enum VfsError {
+    #[error("Not a pack file")]
+    NotAPack,
+    #[error("Unsupported pack version {0}")]
+    UnsupportedVersion(u32),
+    #[error("Corrupt pack: {0}")]
+    Corrupt(String),
+    #[error("Error reading pack: {source}")]
+    IoError {
+        #[from]
+        source: io::Error
+    }
+}
+
+///! Somewhere assets can be read from, a directory, an archive or memory
+pub trait Mount: Send + Sync {
+    ///! None when this mount doesn't have path at all, so the next mount gets a look
+    fn open(&self, path: &str) -> Option<io::Result<Reader>>;
+
+    ///! Whether this mount has path, without opening it
+    fn contains(&self, path: &str) -> bool;
+
+    ///! Modification time used by hot reloading, None when it can't be known
+    fn modified(&self, _path: &str) -> Option<SystemTime> {
+        None
+    }
+}
+
+///! Files under a directory on disk, paths that are absolute or use `..` are refused
+#[derive(Debug,Clone)]
+pub struct Directory {
+    root: PathBuf,
+    confined: bool
+}
+
+impl Directory {
+    pub fn new<P: AsRef<Path>>(root: P) -> Directory {
+        Directory {
+            root: root.as_ref().to_path_buf(),
+            confined: true
+        }
+    }
+
+    //None for paths that would escape root, the working directory mount takes anything
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let inside = Path::new(path).components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+        if inside || !self.confined { Some(self.root.join(path)) } else { None }
+    }
+}
+
+impl Mount for Directory {
+    fn open(&self, path: &str) -> Option<io::Result<Reader>> {
+        if !self.contains(path) {
+            return None
+        }
+        Some(File::open(self.resolve(path)?).map(|f| Box::new(f) as Reader))
+    }
+
+    fn contains(&self, path: &str) -> bool {
+        self.resolve(path).map(|p| p.is_file()).unwrap_or(false)
+    }
+
+    fn modified(&self, path: &str) -> Option<SystemTime> {
+        fs::metadata(self.resolve(path)?).and_then(|m| m.modified()).ok()
+    }
+}
+
+///! Files kept in memory, for embedded assets and tests
+#[derive(Debug,Clone,Default)]
+pub struct Memory {
+    files: HashMap<String, Cow<'static, [u8]>>
+}
+
+impl Memory {
+    pub fn new() -> Memory {
+        Memory::default()
+    }
+
+    pub fn with_file<B: Into<Cow<'static, [u8]>>>(mut self, path: &str, bytes: B) -> Self {
+        self.insert(path, bytes);
+        self
+    }
+
+    pub fn insert<B: Into<Cow<'static, [u8]>>>(&mut self, path: &str, bytes: B) {
+        self.files.insert(path.to_string(), bytes.into());
+    }
+
+    pub fn remove(&mut self, path: &str) -> bool {
+        self.files.remove(path).is_some()
+    }
+}
+
+impl Mount for Memory {
+    fn open(&self, path: &str) -> Option<io::Result<Reader>> {
+        self.files.get(path).map(|bytes| Ok(Box::new(Cursor::new(bytes.clone())) as Reader))
+    }
+
+    fn contains(&self, path: &str) -> bool {
+        self.files.contains_key(path)
+    }
+}
+
+///! Search path of mounts, later mounts override earlier ones.
+///! Mount the base game first, then mods, then user overrides.
+#[derive(Default)]
+pub struct Vfs {
+    mounts: Vec<Box<dyn Mount>>
+}
+
+impl Vfs {
+    pub fn new() -> Vfs {
+        Vfs::default()
+    }
+
+    ///! Paths resolve against the working directory and may be absolute, what Storage uses unless told otherwise
+    pub fn native() -> Vfs {
+        Vfs::new().with_mount(Directory { root: PathBuf::new(), confined: false })
+    }
+
+    pub fn with_mount<M: Mount + 'static>(mut self, mount: M) -> Self {
+        self.mount(Box::new(mount));
+        self
+    }
+
+    ///! Add a mount above every existing one
+    pub fn mount(&mut self, mount: Box<dyn Mount>) {
+        self.mounts.push(mount);
+    }
+
+    pub fn len(&self) -> usize {
+        self.mounts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mounts.is_empty()
+    }
+
+    pub fn open(&self, path: &str) -> io::Result<Reader> {
+        for mount in self.mounts.iter().rev() {
+            if let Some(reader) = mount.open(path) {
+                return reader
+            }
+        }
+        Err(io::Error::new(io::ErrorKind::NotFound, format!("\"{}\" isn't in any mount", path)))
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        self.mounts.iter().any(|m| m.contains(path))
+    }
+
+    ///! Modification time from whichever mount serves path
+    pub fn modified(&self, path: &str) -> Option<SystemTime> {
+        self.mounts.iter().rev().find(|m| m.contains(path)).and_then(|m| m.modified(path))
+    }
+
+    pub fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.open(path)?.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+#[test]
+fn vfs_overrides() {
+    let vfs = Vfs::new()
+        .with_mount(Memory::new().with_file("a.txt", &b"base"[..]).with_file("b.txt", &b"base"[..]))
+        .with_mount(Memory::new().with_file("a.txt", &b"mod"[..]));
+
+    assert_eq!(vfs.read("a.txt").unwrap(), b"mod");
+    assert_eq!(vfs.read("b.txt").unwrap(), b"base");
+    assert!(!vfs.exists("c.txt"));
+    assert_eq!(vfs.open("c.txt").err().unwrap().kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn directory_stays_in_root() {
+    let root = std::env::temp_dir().join("pipewrench_directory_root");
+    fs::create_dir_all(root.join("assets")).unwrap();
+    fs::write(root.join("secret.txt"), b"outside").unwrap();
+    fs::write(root.join("assets/a.txt"), b"inside").unwrap();
+
+    let vfs = Vfs::new().with_mount(Directory::new(root.join("assets")));
+    assert_eq!(vfs.read("./a.txt").unwrap(), b"inside");
+    assert!(vfs.modified("a.txt").is_some());
+    assert!(!vfs.exists("../secret.txt"));
+    assert!(vfs.modified("../secret.txt").is_none());
+    let absolute = root.join("secret.txt").to_string_lossy().into_owned();
+    assert_eq!(vfs.open(&absolute).err().unwrap().kind(), io::ErrorKind::NotFound);
+
+    fs::remove_dir_all(&root).unwrap();
+}